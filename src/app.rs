@@ -2,7 +2,18 @@
 
 use crate::config::Config;
 use crate::credentials::Credentials;
-use crate::post_manager::PostManager;
+use crate::history::{History, HistoryEntry, HistoryTarget};
+use crate::link_preview::{self, LinkPreview, LinkPreviewFetcher};
+use crate::oauth::{self, OAuthTokens};
+use crate::oauth::atproto::AtprotoSession;
+use crate::oauth::mastodon::MastodonSignIn;
+use crate::outbox::{Outbox, OutboxEntry, OutboxState};
+use crate::platforms::{
+    EditMethod, ErrorKind, PlatformId, Post, PostImage, PostOutcome, PostReference, ReferenceKind,
+    RemoteId, ThreadOptions, nostr,
+};
+use crate::post_manager::{PlatformTimeouts, PostManager};
+use crate::thread;
 use cosmic::cosmic_config::{self, CosmicConfigEntry};
 use cosmic::iced::{window::Id, Limits, Subscription};
 use cosmic::iced::widget::text_editor::{Content, Action};
@@ -10,14 +21,7 @@ use cosmic::iced_winit::commands::popup::{destroy_popup, get_popup};
 use cosmic::prelude::*;
 use cosmic::widget;
 use futures_util::SinkExt;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum PlatformType {
-    X,
-    BlueSky,
-    Nostr,
-    Mastodon,
-}
+use std::collections::HashSet;
 
 /// The application model stores app-specific state used to describe its interface and
 /// drive its logic.
@@ -42,6 +46,16 @@ pub struct AppModel {
     show_emoji_picker: bool,
     /// Selected image paths
     image_paths: Vec<std::path::PathBuf>,
+    /// Alt text for each selected image, in the same order
+    image_alts: Vec<String>,
+    /// Metadata of the first link in the draft
+    link_preview: Option<LinkPreview>,
+    /// URL of a post to reply to or quote
+    reference_url: String,
+    quote_reference: bool,
+    /// Split long text into a reply chain
+    post_as_thread: bool,
+    number_thread_parts: bool,
     /// Platform selection
     post_to_x: bool,
     post_to_bluesky: bool,
@@ -49,7 +63,34 @@ pub struct AppModel {
     post_to_mastodon: bool,
     /// Posting status
     posting: bool,
+    /// Proof of work being mined, shared by every post including background
+    /// retries so Cancel stops all of them
+    pow_progress: nostr::PowProgress,
     status_message: String,
+    /// Platforms the current draft has been delivered to
+    draft_delivered: HashSet<PlatformId>,
+    /// Platforms the current draft failed on
+    draft_failed: Vec<PlatformId>,
+    /// Outbox entries queued for the current draft
+    draft_outbox_ids: Vec<String>,
+    /// Delivery id shared by every attempt to send the current draft
+    draft_delivery_id: Option<String>,
+    /// History entry of the current draft, which retries add to
+    draft_history_id: Option<String>,
+    /// Log of published posts
+    history: Option<History>,
+    history_entries: Vec<HistoryEntry>,
+    /// History entry waiting for the user to confirm its deletion
+    confirm_delete: Option<String>,
+    /// History entry currently being deleted
+    deleting: Option<String>,
+    /// Published post loaded into the composer for editing
+    editing: Option<EditState>,
+    /// Failed deliveries waiting to be retried
+    outbox: Option<Outbox>,
+    outbox_entries: Vec<OutboxEntry>,
+    /// Whether a background outbox retry is running
+    flushing_outbox: bool,
     
     // Settings UI State
     // X/Twitter
@@ -57,14 +98,21 @@ pub struct AppModel {
     twitter_consumer_secret: String,
     twitter_access_token: String,
     twitter_access_secret: String,
+    twitter_client_id: String,
     // BlueSky
     bluesky_handle: String,
     bluesky_password: String,
+    bluesky_service_url: String,
+    bluesky_appview_url: String,
     // Nostr
     nostr_nsec: String,
     nostr_relays: String,
     nostr_use_pleb_signer: bool,
+    nostr_bunker_uri: String,
     nostr_blossom_server: String,
+    nostr_viewer_url: String,
+    nostr_file_metadata: bool,
+    nostr_pow_difficulty: String,
     // Mastodon
     mastodon_instance_url: String,
     mastodon_access_token: String,
@@ -74,12 +122,31 @@ pub struct AppModel {
     bluesky_section_expanded: bool,
     nostr_section_expanded: bool,
     mastodon_section_expanded: bool,
+    /// Platform whose browser sign-in is in progress
+    signing_in: Option<PlatformId>,
+}
+
+/// A published post being edited in the composer
+#[derive(Debug, Clone)]
+pub struct EditState {
+    pub entry: HistoryEntry,
+    /// How each platform that still has the post will apply the edit
+    pub methods: Vec<(PlatformId, EditMethod)>,
+}
+
+/// Result of a browser sign-in
+#[derive(Debug, Clone)]
+pub enum SignIn {
+    X(OAuthTokens),
+    BlueSky(AtprotoSession),
+    Mastodon(MastodonSignIn),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ViewState {
     Main,
     Settings,
+    History,
 }
 
 /// Messages emitted by the application and its widgets.
@@ -97,26 +164,69 @@ pub enum Message {
     InsertEmoji(String),
     SelectImages,
     ImagesSelected(Vec<std::path::PathBuf>),
-    TogglePlatform(PlatformType, bool),
+    ImageAltChanged(usize, String),
+    TogglePlatform(PlatformId, bool),
+    ReferenceUrlChanged(String),
+    ToggleQuote(bool),
+    ToggleThread(bool),
+    ToggleThreadNumbering(bool),
+    FetchLinkPreview(String),
+    LinkPreviewLoaded(String, Option<LinkPreview>),
     PostClicked,
-    PostCompleted(Vec<(String, bool, String)>), // (platform, success, message)
+    RetryFailedClicked,
+    PostCompleted(Vec<PostOutcome>, Vec<String>, Option<String>), // (outcomes, queued outbox entry ids, history id)
+    PowTick,
+    CancelPow,
+    
+    // Outbox
+    OutboxTick,
+    OutboxFlushed(Vec<(String, PostOutcome)>), // (outbox entry id, outcome)
+    RetryOutboxEntry(String),
+    DiscardOutboxEntry(String),
+    
+    // History
+    ShowHistory,
+    OpenUrl(String),
+    CopyToClipboard(String),
+    DeleteEverywhere(String),
+    ConfirmDelete(String),
+    CancelDelete,
+    DeleteCompleted(String, Vec<PostOutcome>),
+    EditLastPost,
+    SaveEdit,
+    CancelEdit,
+    EditCompleted(String, String, Vec<PostOutcome>),
     
     // Settings
     ShowSettings,
     ShowMain,
     SaveCredentials,
+    SignIn(PlatformId),
+    SignInCompleted(Result<SignIn, String>),
+    SignOut(PlatformId),
     
     // Credential editing
     TwitterConsumerKeyChanged(String),
     TwitterConsumerSecretChanged(String),
     TwitterAccessTokenChanged(String),
     TwitterAccessSecretChanged(String),
+    TwitterClientIdChanged(String),
     BlueSkyHandleChanged(String),
     BlueSkyPasswordChanged(String),
+    BlueSkyServiceUrlChanged(String),
+    BlueSkyAppViewUrlChanged(String),
     NostrNsecChanged(String),
+    NostrBunkerUriChanged(String),
+    NostrCreateConnectUri,
+    NostrRemoteSignerConnected(Result<String, String>),
     NostrRelaysChanged(String),
+    NostrPublishRelayList,
+    NostrRelayListPublished(Result<usize, String>),
     NostrTogglePlebSigner(bool),
     NostrBlossomServerChanged(String),
+    NostrViewerUrlChanged(String),
+    NostrToggleFileMetadata(bool),
+    NostrPowDifficultyChanged(String),
     MastodonInstanceUrlChanged(String),
     MastodonAccessTokenChanged(String),
     
@@ -162,15 +272,38 @@ impl cosmic::Application for AppModel {
         let twitter_consumer_secret = credentials.twitter_consumer_secret.clone().unwrap_or_default();
         let twitter_access_token = credentials.twitter_access_token.clone().unwrap_or_default();
         let twitter_access_secret = credentials.twitter_access_secret.clone().unwrap_or_default();
+        let twitter_client_id = credentials.twitter_client_id.clone().unwrap_or_default();
         let bluesky_handle = credentials.bluesky_handle.clone().unwrap_or_default();
         let bluesky_password = credentials.bluesky_app_password.clone().unwrap_or_default();
+        let bluesky_service_url = credentials.bluesky_service_url.clone().unwrap_or_default();
+        let bluesky_appview_url = credentials.bluesky_appview_url.clone().unwrap_or_default();
         let nostr_nsec = credentials.nostr_nsec.clone().unwrap_or_default();
         let nostr_relays = credentials.nostr_relays.join(", ");
         let nostr_use_pleb_signer = credentials.nostr_use_pleb_signer;
+        let nostr_bunker_uri = credentials.nostr_bunker_uri.clone().unwrap_or_default();
         let nostr_blossom_server = credentials.nostr_image_host_url.clone().unwrap_or_default();
+        let nostr_viewer_url = credentials.nostr_viewer_url.clone().unwrap_or_default();
+        let nostr_file_metadata = credentials.nostr_file_metadata;
+        let nostr_pow_difficulty = match credentials.nostr_pow_difficulty {
+            0 => String::new(),
+            difficulty => difficulty.to_string(),
+        };
         let mastodon_instance_url = credentials.mastodon_instance_url.clone().unwrap_or_default();
         let mastodon_access_token = credentials.mastodon_access_token.clone().unwrap_or_default();
         
+        // Open the outbox of deliveries still waiting to be retried
+        let outbox = Outbox::open_default()
+            .map_err(|e| eprintln!("[App] Failed to open outbox: {}", e))
+            .ok();
+        if let Some(outbox) = &outbox {
+            outbox.recover_interrupted();
+        }
+        let outbox_entries = outbox.as_ref().map(Outbox::list).unwrap_or_default();
+        
+        let history = History::open_default()
+            .map_err(|e| eprintln!("[App] Failed to open history: {}", e))
+            .ok();
+        
         // Load config
         let config = cosmic_config::Config::new(Self::APP_ID, Config::VERSION)
             .map(|context| match Config::get_entry(&context) {
@@ -188,30 +321,58 @@ impl cosmic::Application for AppModel {
             post_text: String::new(),
             text_editor_content: Content::new(),
             show_emoji_picker: false,
+            reference_url: String::new(),
+            quote_reference: false,
+            post_as_thread: false,
+            number_thread_parts: true,
             image_paths: Vec::new(),
+            image_alts: Vec::new(),
+            link_preview: None,
             post_to_x: config.post_to_x,
             post_to_bluesky: config.post_to_bluesky,
             post_to_nostr: config.post_to_nostr,
             post_to_mastodon: config.post_to_mastodon,
             posting: false,
+            pow_progress: nostr::PowProgress::default(),
             status_message: String::new(),
+            draft_delivered: HashSet::new(),
+            draft_failed: Vec::new(),
+            draft_outbox_ids: Vec::new(),
+            draft_delivery_id: None,
+            draft_history_id: None,
+            history,
+            history_entries: Vec::new(),
+            confirm_delete: None,
+            deleting: None,
+            editing: None,
+            outbox,
+            outbox_entries,
+            flushing_outbox: false,
             config,
             twitter_consumer_key,
             twitter_consumer_secret,
             twitter_access_token,
             twitter_access_secret,
+            twitter_client_id,
             bluesky_handle,
             bluesky_password,
+            bluesky_service_url,
+            bluesky_appview_url,
             nostr_nsec,
             nostr_relays,
             nostr_use_pleb_signer,
+            nostr_bunker_uri,
             nostr_blossom_server,
+            nostr_viewer_url,
+            nostr_file_metadata,
+            nostr_pow_difficulty,
             mastodon_instance_url,
             mastodon_access_token,
             twitter_section_expanded: false,
             bluesky_section_expanded: false,
             nostr_section_expanded: false,
             mastodon_section_expanded: false,
+            signing_in: None,
         };
 
         (app, Task::none())
@@ -241,6 +402,7 @@ impl cosmic::Application for AppModel {
         let content = match self.current_view {
             ViewState::Main => self.view_main(),
             ViewState::Settings => self.view_settings(),
+            ViewState::History => self.view_history(),
         };
         
         self.core.applet.popup_container(content).into()
@@ -254,8 +416,10 @@ impl cosmic::Application for AppModel {
     /// continue to execute for the duration that they remain in the batch.
     fn subscription(&self) -> Subscription<Self::Message> {
         struct MySubscription;
+        struct OutboxSubscription;
+        struct PowSubscription;
 
-        Subscription::batch(vec![
+        let mut subscriptions = vec![
             // Create a subscription which emits updates through a channel.
             Subscription::run_with_id(
                 std::any::TypeId::of::<MySubscription>(),
@@ -265,6 +429,17 @@ impl cosmic::Application for AppModel {
                     futures_util::future::pending().await
                 }),
             ),
+            // Periodically retry deliveries waiting in the outbox.
+            Subscription::run_with_id(
+                std::any::TypeId::of::<OutboxSubscription>(),
+                cosmic::iced::stream::channel(1, move |mut channel| async move {
+                    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+                    loop {
+                        interval.tick().await;
+                        _ = channel.send(Message::OutboxTick).await;
+                    }
+                }),
+            ),
             // Watch for application configuration changes.
             self.core()
                 .watch_config::<Config>(Self::APP_ID)
@@ -275,7 +450,23 @@ impl cosmic::Application for AppModel {
 
                     Message::UpdateConfig(update.config)
                 }),
-        ])
+        ];
+        
+        // Refresh proof-of-work progress while a post may be mining
+        if (self.posting || self.flushing_outbox) && self.credentials.nostr_pow_difficulty > 0 {
+            subscriptions.push(Subscription::run_with_id(
+                std::any::TypeId::of::<PowSubscription>(),
+                cosmic::iced::stream::channel(1, move |mut channel| async move {
+                    let mut interval = tokio::time::interval(std::time::Duration::from_millis(250));
+                    loop {
+                        interval.tick().await;
+                        _ = channel.send(Message::PowTick).await;
+                    }
+                }),
+            ));
+        }
+        
+        Subscription::batch(subscriptions)
     }
 
     /// Handles messages emitted by the application and its widgets.
@@ -292,11 +483,54 @@ impl cosmic::Application for AppModel {
                 self.config = config;
             }
             Message::TextChanged(text) => {
+                if text != self.post_text {
+                    self.draft_changed();
+                }
                 self.post_text = text;
+                return self.refresh_link_preview();
             }
             Message::TextEditorAction(action) => {
                 self.text_editor_content.perform(action);
-                self.post_text = self.text_editor_content.text();
+                let text = self.text_editor_content.text();
+                // Cursor movement and selection leave the text alone
+                if text != self.post_text {
+                    self.draft_changed();
+                }
+                self.post_text = text;
+                return self.refresh_link_preview();
+            }
+            Message::ReferenceUrlChanged(value) => {
+                self.reference_url = value;
+                self.draft_changed();
+            }
+            Message::ToggleQuote(value) => {
+                self.quote_reference = value;
+                self.draft_changed();
+            }
+            Message::ToggleThread(value) => {
+                self.post_as_thread = value;
+            }
+            Message::ToggleThreadNumbering(value) => {
+                self.number_thread_parts = value;
+            }
+            Message::FetchLinkPreview(url) => {
+                // The text moved on while we waited
+                if link_preview::first_url(&self.post_text) != Some(url.as_str()) {
+                    return Task::none();
+                }
+                return Task::future(async move {
+                    let preview = LinkPreviewFetcher::new()
+                        .fetch(&url)
+                        .await
+                        .map_err(|e| eprintln!("[App] Failed to fetch link preview: {}", e))
+                        .ok();
+                    cosmic::Action::App(Message::LinkPreviewLoaded(url, preview))
+                });
+            }
+            Message::LinkPreviewLoaded(url, preview) => {
+                if link_preview::first_url(&self.post_text) == Some(url.as_str()) {
+                    self.link_preview = preview;
+                }
             }
             Message::ToggleEmojiPicker => {
                 self.show_emoji_picker = !self.show_emoji_picker;
@@ -308,6 +542,8 @@ impl cosmic::Application for AppModel {
                 self.text_editor_content = Content::with_text(&new_text);
                 self.post_text = new_text;
                 self.show_emoji_picker = false;
+                self.draft_changed();
+                return self.refresh_link_preview();
             }
             Message::SelectImages => {
                 return Task::future(
@@ -324,14 +560,22 @@ impl cosmic::Application for AppModel {
                 );
             }
             Message::ImagesSelected(paths) => {
+                self.image_alts = vec![String::new(); paths.len()];
                 self.image_paths = paths;
+                self.draft_changed();
+            }
+            Message::ImageAltChanged(index, alt) => {
+                if let Some(image_alt) = self.image_alts.get_mut(index) {
+                    *image_alt = alt;
+                }
+                self.draft_changed();
             }
             Message::TogglePlatform(platform, enabled) => {
                 match platform {
-                    PlatformType::X => self.post_to_x = enabled,
-                    PlatformType::BlueSky => self.post_to_bluesky = enabled,
-                    PlatformType::Nostr => self.post_to_nostr = enabled,
-                    PlatformType::Mastodon => self.post_to_mastodon = enabled,
+                    PlatformId::X => self.post_to_x = enabled,
+                    PlatformId::BlueSky => self.post_to_bluesky = enabled,
+                    PlatformId::Nostr => self.post_to_nostr = enabled,
+                    PlatformId::Mastodon => self.post_to_mastodon = enabled,
                 }
                 
                 // Update config to persist platform selections
@@ -346,6 +590,10 @@ impl cosmic::Application for AppModel {
                 }
             }
             Message::PostClicked => {
+                if self.pow_progress.mining().is_some() {
+                    self.status_message = "Wait for the proof of work to finish or cancel it".to_string();
+                    return Task::none();
+                }
                 if self.post_text.is_empty() {
                     self.status_message = "Please enter some text".to_string();
                     return Task::none();
@@ -361,56 +609,296 @@ impl cosmic::Application for AppModel {
                     return Task::none();
                 }
                 
-                self.posting = true;
-                self.status_message = "Posting...".to_string();
-                
-                // Collect selected platforms
-                let mut platforms = Vec::new();
-                if self.post_to_x { platforms.push("X".to_string()); }
-                if self.post_to_bluesky { platforms.push("BlueSky".to_string()); }
-                if self.post_to_nostr { platforms.push("Nostr".to_string()); }
-                if self.post_to_mastodon { platforms.push("Mastodon".to_string()); }
-                
-                let text = self.post_text.clone();
-                let image_paths = self.image_paths.clone();
-                let credentials = self.credentials.clone();
-                
-                return Task::future(async move {
-                    // Load images from paths
-                    let mut images = Vec::new();
-                    for path in image_paths {
-                        match std::fs::read(&path) {
-                            Ok(bytes) => {
-                                eprintln!("[App] Loaded image: {} ({} bytes)", path.display(), bytes.len());
-                                images.push(bytes);
-                            }
-                            Err(e) => {
-                                eprintln!("[App] Failed to load image {}: {}", path.display(), e);
-                            }
-                        }
-                    }
-                    
-                    let manager = PostManager::new(credentials);
-                    let results = manager.post(text, images, platforms).await;
-                    cosmic::Action::App(Message::PostCompleted(results))
-                });
+                // Never send the same draft to a platform twice
+                let platforms: Vec<PlatformId> = self
+                    .selected_platforms()
+                    .into_iter()
+                    .filter(|platform| !self.draft_delivered.contains(platform))
+                    .collect();
+                if platforms.is_empty() {
+                    self.status_message = "Already posted to the selected platforms".to_string();
+                    return Task::none();
+                }
+                let platforms = self.drop_queued_draft(platforms);
+                if platforms.is_empty() {
+                    self.status_message = "Already being sent from the outbox".to_string();
+                    return Task::none();
+                }
+                return self.start_post(platforms);
             }
-            Message::PostCompleted(results) => {
+            Message::RetryFailedClicked => {
+                if self.pow_progress.mining().is_some() {
+                    self.status_message = "Wait for the proof of work to finish or cancel it".to_string();
+                    return Task::none();
+                }
+                let platforms = self.drop_queued_draft(self.draft_failed.clone());
+                if platforms.is_empty() {
+                    self.status_message = "Already being sent from the outbox".to_string();
+                    return Task::none();
+                }
+                return self.start_post(platforms);
+            }
+            Message::PostCompleted(results, queued, history_id) => {
                 self.posting = false;
-                let success_count = results.iter().filter(|(_, success, _)| *success).count();
+                self.draft_outbox_ids.extend(queued);
+                if history_id.is_some() {
+                    self.draft_history_id = history_id;
+                }
+                for outcome in &results {
+                    // What a broken-off thread published can't be sent again
+                    if outcome.is_success() || outcome.partial {
+                        self.draft_delivered.insert(outcome.platform);
+                        self.draft_failed.retain(|platform| *platform != outcome.platform);
+                    } else if !self.draft_failed.contains(&outcome.platform) {
+                        self.draft_failed.push(outcome.platform);
+                    }
+                }
+                let success_count = results.iter().filter(|outcome| outcome.is_success()).count();
                 let total = results.len();
+                let failures: Vec<String> = results
+                    .iter()
+                    .filter(|outcome| !outcome.partial)
+                    .filter_map(|outcome| {
+                        outcome.error.as_ref().map(|e| format!("{} ({})", outcome.platform, e.kind))
+                    })
+                    .collect();
+                let partial: Vec<&str> = results
+                    .iter()
+                    .filter(|outcome| outcome.partial)
+                    .map(|outcome| outcome.platform.name())
+                    .collect();
                 self.status_message = format!(
-                    "Posted to {}/{} platforms. {}",
+                    "Posted to {}/{} platforms. {}{}",
                     success_count,
                     total,
-                    if success_count < total { "Check logs for errors." } else { "" }
+                    if partial.is_empty() { String::new() } else { format!("Thread partially posted: {}. ", partial.join(", ")) },
+                    if failures.is_empty() { String::new() } else { format!("Failed: {}", failures.join(", ")) }
                 );
+                for outcome in results.iter().filter(|outcome| outcome.is_success()) {
+                    if let Some(summary) = outcome.relay_summary() {
+                        self.status_message = format!("{} {}: {}.", self.status_message.trim_end(), outcome.platform, summary);
+                    }
+                }
+                self.reload_outbox();
+                
+                // Clear input box once every platform has the post. Keep it
+                // after a broken-off thread so the rest can be posted by hand.
+                if self.draft_failed.is_empty() && partial.is_empty() && total > 0 {
+                    self.clear_draft();
+                }
+            }
+            Message::PowTick => {
+                // Redrawn with the latest mining progress
+            }
+            Message::CancelPow => {
+                self.pow_progress.cancel();
+                self.status_message = "Cancelling proof of work...".to_string();
+            }
+            Message::OutboxTick => {
+                return self.flush_outbox();
+            }
+            Message::OutboxFlushed(outcomes) => {
+                self.flushing_outbox = false;
+                let mut delivered = Vec::new();
+                for (entry, outcome) in outcomes.iter().filter(|(_, outcome)| outcome.is_success()) {
+                    delivered.push(outcome.platform.to_string());
+                    
+                    if let (Some(history), Some(history_id)) = (&self.history, &entry.history_id) {
+                        if let Err(e) = history.add_target(history_id, HistoryTarget::from_outcome(outcome)) {
+                            eprintln!("[App] Failed to update history {}: {}", history_id, e);
+                        }
+                    }
+                    
+                    // A queued copy of the current draft got through
+                    if let Some(index) = self.draft_outbox_ids.iter().position(|queued| *queued == entry.id) {
+                        self.draft_outbox_ids.remove(index);
+                        self.draft_delivered.insert(outcome.platform);
+                        self.draft_failed.retain(|platform| *platform != outcome.platform);
+                    }
+                }
+                if !delivered.is_empty() {
+                    self.status_message = format!("Delivered queued posts to {}", delivered.join(", "));
+                }
+                self.reload_outbox();
+            }
+            Message::RetryOutboxEntry(id) => {
+                if let Some(outbox) = &self.outbox {
+                    if let Err(e) = outbox.retry_now(&id) {
+                        eprintln!("[App] Failed to retry outbox entry {}: {}", id, e);
+                    }
+                }
+                self.reload_outbox();
+                return self.flush_outbox();
+            }
+            Message::DiscardOutboxEntry(id) => {
+                if let Some(outbox) = &self.outbox {
+                    if let Err(e) = outbox.remove(&id) {
+                        eprintln!("[App] Failed to discard outbox entry {}: {}", id, e);
+                    }
+                }
+                self.reload_outbox();
+            }
+            Message::ShowHistory => {
+                self.history_entries = self.history.as_ref().map(History::list).unwrap_or_default();
+                self.current_view = ViewState::History;
+            }
+            Message::OpenUrl(url) => {
+                if let Err(e) = std::process::Command::new("xdg-open").arg(&url).spawn() {
+                    eprintln!("[App] Failed to open {}: {}", url, e);
+                    self.status_message = format!("Failed to open link: {}", e);
+                }
+            }
+            Message::CopyToClipboard(contents) => {
+                self.status_message = "Copied to clipboard".to_string();
+                return cosmic::iced::clipboard::write(contents);
+            }
+            Message::DeleteEverywhere(id) => {
+                self.confirm_delete = Some(id);
+            }
+            Message::CancelDelete => {
+                self.confirm_delete = None;
+            }
+            Message::ConfirmDelete(id) => {
+                self.confirm_delete = None;
+                return self.delete_everywhere(id);
+            }
+            Message::DeleteCompleted(id, results) => {
+                self.deleting = None;
+                
+                // A platform only counts as done once every part of a thread is gone
+                let mut deleted: Vec<PlatformId> = results.iter().map(|outcome| outcome.platform).collect();
+                deleted.dedup();
+                deleted.retain(|platform| {
+                    results
+                        .iter()
+                        .filter(|outcome| outcome.platform == *platform)
+                        .all(PostOutcome::is_success)
+                });
+                if let Some(history) = &self.history {
+                    if let Err(e) = history.mark_deleted(&id, &deleted) {
+                        eprintln!("[App] Failed to update history {}: {}", id, e);
+                    }
+                    self.history_entries = history.list();
+                }
+                
+                let failures: Vec<String> = results
+                    .iter()
+                    .filter_map(|outcome| {
+                        outcome.error.as_ref().map(|e| format!("{} ({})", outcome.platform, e.kind))
+                    })
+                    .collect();
+                self.status_message = format!(
+                    "Deleted from {}/{} platforms. {}",
+                    deleted.len(),
+                    results.len(),
+                    if failures.is_empty() { String::new() } else { format!("Failed: {}", failures.join(", ")) }
+                );
+            }
+            Message::EditLastPost => {
+                if !self.post_text.is_empty() || !self.image_paths.is_empty() {
+                    self.status_message = "Post or clear the current draft before editing".to_string();
+                    return Task::none();
+                }
+                
+                let Some(entry) = self
+                    .history
+                    .as_ref()
+                    .map(History::list)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|entry| entry.targets.iter().any(|target| target.is_deletable()))
+                else {
+                    self.status_message = "No published post to edit".to_string();
+                    return Task::none();
+                };
+                if entry.targets.iter().any(|target| !target.thread_ids.is_empty()) {
+                    self.status_message = "Posts published as a thread can't be edited".to_string();
+                    return Task::none();
+                }
+                
+                let manager = PostManager::new(self.credentials.clone());
+                let methods = entry
+                    .targets
+                    .iter()
+                    .filter(|target| target.is_deletable())
+                    .filter_map(|target| Some((target.platform, manager.edit_method(target.platform)?)))
+                    .collect();
+                
+                self.text_editor_content = Content::with_text(&entry.text);
+                self.post_text = entry.text.clone();
+                self.editing = Some(EditState { entry, methods });
+                self.current_view = ViewState::Main;
+                self.status_message.clear();
+                return self.refresh_link_preview();
+            }
+            Message::CancelEdit => {
+                self.editing = None;
+                self.clear_draft();
+            }
+            Message::SaveEdit => {
+                if self.pow_progress.mining().is_some() {
+                    self.status_message = "Wait for the proof of work to finish or cancel it".to_string();
+                    return Task::none();
+                }
+                if self.post_text.is_empty() {
+                    self.status_message = "Please enter some text".to_string();
+                    return Task::none();
+                }
+                return self.save_edit();
+            }
+            Message::EditCompleted(id, text, results) => {
+                self.posting = false;
+                
+                if results.iter().any(|outcome| outcome.is_success() || outcome.partial) {
+                    if let Some(history) = &self.history {
+                        if let Err(e) = history.record_edit(&id, &text, &results) {
+                            eprintln!("[App] Failed to update history {}: {}", id, e);
+                        }
+                    }
+                }
                 
-                // Clear input box if all posts were successful
-                if success_count == total && total > 0 {
-                    self.post_text.clear();
-                    self.text_editor_content = Content::new();
-                    self.image_paths.clear();
+                let methods = self.editing.as_ref().map(|editing| editing.methods.clone()).unwrap_or_default();
+                let succeeded = |method: EditMethod| -> Vec<&str> {
+                    results
+                        .iter()
+                        .filter(|outcome| outcome.is_success())
+                        .filter(|outcome| methods.contains(&(outcome.platform, method)))
+                        .map(|outcome| outcome.platform.name())
+                        .collect()
+                };
+                let (in_place, reposted) = (succeeded(EditMethod::InPlace), succeeded(EditMethod::Repost));
+                let failures: Vec<String> = results
+                    .iter()
+                    .filter(|outcome| !outcome.partial)
+                    .filter_map(|outcome| {
+                        outcome.error.as_ref().map(|e| format!("{} ({})", outcome.platform, e.kind))
+                    })
+                    .collect();
+                let undeleted: Vec<&str> = results
+                    .iter()
+                    .filter(|outcome| outcome.partial)
+                    .map(|outcome| outcome.platform.name())
+                    .collect();
+                
+                let mut parts = Vec::new();
+                if !in_place.is_empty() {
+                    parts.push(format!("Edited on {}", in_place.join(", ")));
+                }
+                if !reposted.is_empty() {
+                    parts.push(format!("Deleted and reposted on {}", reposted.join(", ")));
+                }
+                if !undeleted.is_empty() {
+                    parts.push(format!("Reposted, but the original is still up on {}", undeleted.join(", ")));
+                }
+                if !failures.is_empty() {
+                    parts.push(format!("Failed: {}", failures.join(", ")));
+                }
+                self.status_message = parts.join(". ");
+                
+                // Keep the edit open if nothing took it, so it can be retried
+                if !in_place.is_empty() || !reposted.is_empty() || !undeleted.is_empty() {
+                    self.editing = None;
+                    self.clear_draft();
                 }
             }
             Message::ShowSettings => {
@@ -420,7 +908,10 @@ impl cosmic::Application for AppModel {
                 self.current_view = ViewState::Main;
             }
             Message::SaveCredentials => {
-                eprintln!("[GUI] SaveCredentials button clicked!");
+                // Tokens may have been refreshed since the settings were loaded
+                if let Ok(stored) = Credentials::load() {
+                    self.credentials.keep_tokens_from(&stored);
+                }
                 // Update credentials from UI fields
                 self.credentials.twitter_consumer_key = if self.twitter_consumer_key.is_empty() {
                     None
@@ -442,6 +933,11 @@ impl cosmic::Application for AppModel {
                 } else {
                     Some(self.twitter_access_secret.clone())
                 };
+                self.credentials.twitter_client_id = if self.twitter_client_id.is_empty() {
+                    None
+                } else {
+                    Some(self.twitter_client_id.clone())
+                };
                 self.credentials.bluesky_handle = if self.bluesky_handle.is_empty() {
                     None
                 } else {
@@ -452,6 +948,16 @@ impl cosmic::Application for AppModel {
                 } else {
                     Some(self.bluesky_password.clone())
                 };
+                self.credentials.bluesky_service_url = if self.bluesky_service_url.is_empty() {
+                    None
+                } else {
+                    Some(self.bluesky_service_url.clone())
+                };
+                self.credentials.bluesky_appview_url = if self.bluesky_appview_url.trim().is_empty() {
+                    None
+                } else {
+                    Some(self.bluesky_appview_url.trim().to_string())
+                };
                 self.credentials.nostr_nsec = if self.nostr_nsec.is_empty() {
                     None
                 } else {
@@ -463,11 +969,29 @@ impl cosmic::Application for AppModel {
                     self.nostr_relays.split(',').map(|s| s.trim().to_string()).collect()
                 };
                 self.credentials.nostr_use_pleb_signer = self.nostr_use_pleb_signer;
+                self.credentials.nostr_bunker_uri = if self.nostr_bunker_uri.trim().is_empty() {
+                    None
+                } else {
+                    Some(self.nostr_bunker_uri.trim().to_string())
+                };
+                if self.credentials.nostr_bunker_uri.is_some() {
+                    let app_key = self.ensure_bunker_app_key();
+                    if let Err(e) = app_key {
+                        eprintln!("[GUI] Failed to create remote signer key: {}", e);
+                    }
+                }
                 self.credentials.nostr_image_host_url = if self.nostr_blossom_server.is_empty() {
                     None
                 } else {
                     Some(self.nostr_blossom_server.clone())
                 };
+                self.credentials.nostr_viewer_url = if self.nostr_viewer_url.is_empty() {
+                    None
+                } else {
+                    Some(self.nostr_viewer_url.clone())
+                };
+                self.credentials.nostr_file_metadata = self.nostr_file_metadata;
+                self.credentials.nostr_pow_difficulty = self.nostr_pow_difficulty.trim().parse().unwrap_or(0);
                 self.credentials.mastodon_instance_url = if self.mastodon_instance_url.is_empty() {
                     None
                 } else {
@@ -479,17 +1003,91 @@ impl cosmic::Application for AppModel {
                     Some(self.mastodon_access_token.clone())
                 };
                 
-                eprintln!("[GUI] About to call credentials.save()");
-                eprintln!("[GUI] nostr_use_pleb_signer = {}", self.credentials.nostr_use_pleb_signer);
-                eprintln!("[GUI] nostr_nsec = {:?}", self.credentials.nostr_nsec);
                 if let Err(e) = self.credentials.save() {
                     eprintln!("[GUI] Save FAILED: {}", e);
                     self.status_message = format!("Failed to save credentials: {}", e);
                 } else {
-                    eprintln!("[GUI] Save SUCCESS!");
                     self.status_message = "Credentials saved!".to_string();
                 }
             }
+            Message::SignIn(platform) => {
+                self.signing_in = Some(platform);
+                self.status_message = "Continue signing in in your browser".to_string();
+                let twitter_client_id = self.twitter_client_id.clone();
+                let bluesky_handle = self.bluesky_handle.clone();
+                let bluesky_service_url = self.bluesky_service_url.clone();
+                let mastodon_instance_url = self.mastodon_instance_url.clone();
+                return Task::future(async move {
+                    let result = match platform {
+                        PlatformId::X => oauth::x::sign_in(&twitter_client_id).await.map(SignIn::X),
+                        PlatformId::BlueSky => oauth::atproto::sign_in(&bluesky_handle, Some(&bluesky_service_url))
+                            .await
+                            .map(SignIn::BlueSky),
+                        PlatformId::Mastodon => oauth::mastodon::sign_in(&mastodon_instance_url).await.map(SignIn::Mastodon),
+                        PlatformId::Nostr => Err(anyhow::anyhow!("Nostr has no browser sign-in")),
+                    };
+                    cosmic::Action::App(Message::SignInCompleted(result.map_err(|e| e.to_string())))
+                });
+            }
+            Message::SignInCompleted(result) => {
+                self.signing_in = None;
+                let sign_in = match result {
+                    Ok(sign_in) => sign_in,
+                    Err(e) => {
+                        eprintln!("[GUI] Sign-in failed: {}", e);
+                        self.status_message = format!("Sign-in failed: {}", e);
+                        return Task::none();
+                    }
+                };
+                
+                if let Ok(stored) = Credentials::load() {
+                    self.credentials.keep_tokens_from(&stored);
+                }
+                let platform = match sign_in {
+                    SignIn::X(tokens) => {
+                        self.credentials.twitter_client_id = Some(self.twitter_client_id.trim().to_string());
+                        self.credentials.twitter_oauth = Some(tokens);
+                        PlatformId::X
+                    }
+                    SignIn::BlueSky(session) => {
+                        self.credentials.bluesky_handle = Some(self.bluesky_handle.trim().to_string());
+                        self.credentials.bluesky_oauth = Some(session);
+                        PlatformId::BlueSky
+                    }
+                    SignIn::Mastodon(sign_in) => {
+                        self.mastodon_access_token = sign_in.access_token.clone();
+                        self.credentials.mastodon_instance_url = Some(self.mastodon_instance_url.trim().to_string());
+                        self.credentials.mastodon_access_token = Some(sign_in.access_token);
+                        self.credentials.mastodon_client_id = Some(sign_in.client_id);
+                        self.credentials.mastodon_client_secret = Some(sign_in.client_secret);
+                        PlatformId::Mastodon
+                    }
+                };
+                self.status_message = match self.credentials.save() {
+                    Ok(()) => format!("Signed in to {}", platform),
+                    Err(e) => format!("Signed in to {}, but saving failed: {}", platform, e),
+                };
+            }
+            Message::SignOut(platform) => {
+                if let Ok(stored) = Credentials::load() {
+                    self.credentials.keep_tokens_from(&stored);
+                }
+                match platform {
+                    PlatformId::X => self.credentials.twitter_oauth = None,
+                    PlatformId::BlueSky => self.credentials.bluesky_oauth = None,
+                    PlatformId::Mastodon => {
+                        self.mastodon_access_token.clear();
+                        self.credentials.mastodon_access_token = None;
+                        self.credentials.mastodon_client_id = None;
+                        self.credentials.mastodon_client_secret = None;
+                    }
+                    PlatformId::Nostr => {}
+                }
+                self.status_message = match self.credentials.save() {
+                    Ok(()) => format!("Signed out of {}", platform),
+                    Err(e) => format!("Failed to save credentials: {}", e),
+                };
+            }
             Message::TwitterConsumerKeyChanged(value) => {
                 self.twitter_consumer_key = value;
             }
@@ -502,24 +1100,108 @@ impl cosmic::Application for AppModel {
             Message::TwitterAccessSecretChanged(value) => {
                 self.twitter_access_secret = value;
             }
+            Message::TwitterClientIdChanged(value) => {
+                self.twitter_client_id = value;
+            }
             Message::BlueSkyHandleChanged(value) => {
                 self.bluesky_handle = value;
             }
             Message::BlueSkyPasswordChanged(value) => {
                 self.bluesky_password = value;
             }
+            Message::BlueSkyServiceUrlChanged(value) => {
+                self.bluesky_service_url = value;
+            }
+            Message::BlueSkyAppViewUrlChanged(value) => {
+                self.bluesky_appview_url = value;
+            }
             Message::NostrNsecChanged(value) => {
                 self.nostr_nsec = value;
             }
+            Message::NostrBunkerUriChanged(value) => {
+                self.nostr_bunker_uri = value;
+            }
+            Message::NostrCreateConnectUri => {
+                let relays: Vec<String> = if self.nostr_relays.trim().is_empty() {
+                    nostr::DEFAULT_RELAYS.iter().map(|relay| relay.to_string()).collect()
+                } else {
+                    self.nostr_relays.split(',').map(|s| s.trim().to_string()).collect()
+                };
+                let app_key = match self.ensure_bunker_app_key() {
+                    Ok(app_key) => app_key,
+                    Err(e) => {
+                        self.status_message = format!("Failed to create link: {}", e);
+                        return Task::none();
+                    }
+                };
+                let uri = match nostr::nostrconnect_uri(&app_key, &relays) {
+                    Ok(uri) => uri,
+                    Err(e) => {
+                        self.status_message = format!("Failed to create link: {}", e);
+                        return Task::none();
+                    }
+                };
+                
+                // Relays don't keep the signer's answer, so listen for it now
+                self.nostr_bunker_uri = uri.clone();
+                self.status_message = "Paste the link into your signer...".to_string();
+                return Task::future(async move {
+                    let result = nostr::accept_remote_signer(&uri, &app_key)
+                        .await
+                        .map_err(|e| e.to_string());
+                    cosmic::Action::App(Message::NostrRemoteSignerConnected(result))
+                });
+            }
+            Message::NostrRemoteSignerConnected(result) => {
+                match result {
+                    Ok(bunker_uri) => {
+                        self.nostr_bunker_uri = bunker_uri;
+                        self.status_message = "Remote signer connected. Save to use it.".to_string();
+                    }
+                    Err(e) => {
+                        eprintln!("[GUI] Remote signer didn't connect: {}", e);
+                        self.status_message = format!("Remote signer didn't connect: {}", e);
+                    }
+                }
+            }
             Message::NostrRelaysChanged(value) => {
                 self.nostr_relays = value;
             }
+            Message::NostrPublishRelayList => {
+                self.status_message = "Publishing relay list...".to_string();
+                let credentials = self.credentials.clone();
+                return Task::future(async move {
+                    let result = nostr::publish_relay_list(&credentials)
+                        .await
+                        .map(|statuses| statuses.iter().filter(|status| status.accepted).count())
+                        .map_err(|e| e.to_string());
+                    cosmic::Action::App(Message::NostrRelayListPublished(result))
+                });
+            }
+            Message::NostrRelayListPublished(result) => {
+                self.status_message = match result {
+                    Ok(accepted) => format!("Relay list published to {} relays", accepted),
+                    Err(e) => {
+                        eprintln!("[GUI] Failed to publish relay list: {}", e);
+                        format!("Failed to publish relay list: {}", e)
+                    }
+                };
+            }
             Message::NostrTogglePlebSigner(value) => {
                 self.nostr_use_pleb_signer = value;
             }
             Message::NostrBlossomServerChanged(value) => {
                 self.nostr_blossom_server = value;
             }
+            Message::NostrViewerUrlChanged(value) => {
+                self.nostr_viewer_url = value;
+            }
+            Message::NostrToggleFileMetadata(value) => {
+                self.nostr_file_metadata = value;
+            }
+            Message::NostrPowDifficultyChanged(value) => {
+                self.nostr_pow_difficulty = value;
+            }
             Message::ToggleTwitterSection => {
                 self.twitter_section_expanded = !self.twitter_section_expanded;
             }
@@ -574,6 +1256,371 @@ impl cosmic::Application for AppModel {
 }
 
 impl AppModel {
+    /// Platforms currently toggled on, in display order
+    fn selected_platforms(&self) -> Vec<PlatformId> {
+        PlatformId::ALL
+            .into_iter()
+            .filter(|platform| match platform {
+                PlatformId::X => self.post_to_x,
+                PlatformId::BlueSky => self.post_to_bluesky,
+                PlatformId::Nostr => self.post_to_nostr,
+                PlatformId::Mastodon => self.post_to_mastodon,
+            })
+            .collect()
+    }
+    
+    /// Send the current draft to `platforms`
+    fn start_post(&mut self, platforms: Vec<PlatformId>) -> Task<cosmic::Action<Message>> {
+        self.posting = true;
+        self.status_message = "Posting...".to_string();
+        
+        let text = self.post_text.clone();
+        let thread = self.thread_options();
+        let reference = self.reference();
+        let selected_images = self.selected_images();
+        let credentials = self.credentials.clone();
+        let timeouts = PlatformTimeouts::from(&self.config);
+        let outbox = self.outbox.clone();
+        let history = self.history.clone();
+        let draft_history_id = self.draft_history_id.clone();
+        let delivery_id = self
+            .draft_delivery_id
+            .get_or_insert_with(|| chrono::Utc::now().format("%Y%m%dT%H%M%S%.6f").to_string())
+            .clone();
+        let pow_progress = self.pow_progress.clone();
+        
+        Task::future(async move {
+            // Load images from paths
+            let mut images = Vec::new();
+            for (path, alt) in selected_images {
+                match std::fs::read(&path) {
+                    Ok(bytes) => {
+                        eprintln!("[App] Loaded image: {} ({} bytes)", path.display(), bytes.len());
+                        images.push(PostImage::new(bytes, alt));
+                    }
+                    Err(e) => {
+                        eprintln!("[App] Failed to load image {}: {}", path.display(), e);
+                    }
+                }
+            }
+            
+            let manager = PostManager::with_pow_progress(credentials, pow_progress).with_timeouts(timeouts);
+            let post = Post {
+                reference,
+                thread,
+                delivery_id: Some(delivery_id),
+                ..Post::new(text, images)
+            };
+            let results = manager.post(post.clone(), platforms).await;
+            
+            // A retry adds to the draft's entry rather than logging it again
+            let history_id = history.and_then(|history| match draft_history_id {
+                Some(id) => {
+                    for outcome in results.iter().filter(|outcome| outcome.is_success() || outcome.remote_id.is_some()) {
+                        if let Err(e) = history.add_target(&id, HistoryTarget::from_outcome(outcome)) {
+                            eprintln!("[App] Failed to update history {}: {}", id, e);
+                        }
+                    }
+                    Some(id)
+                }
+                None => history
+                    .record(&post.text, &post.images, &results)
+                    .map_err(|e| eprintln!("[App] Failed to record history: {}", e))
+                    .ok()
+                    .map(|entry| entry.id),
+            });
+            
+            // Keep failed deliveries so they can be retried later
+            let mut queued = Vec::new();
+            if let Some(outbox) = outbox {
+                for outcome in &results {
+                    let Some(error) = &outcome.error else { continue };
+                    // A thread that broke off midway can't be resent without duplicates
+                    if matches!(error.kind, ErrorKind::NotConfigured | ErrorKind::Cancelled) || outcome.partial {
+                        continue;
+                    }
+                    match outbox.enqueue(outcome.platform, &post, error, history_id.clone()) {
+                        Ok(entry) => queued.push(entry.id),
+                        Err(e) => eprintln!("[App] Failed to queue {} delivery: {}", outcome.platform, e),
+                    }
+                }
+            }
+            
+            cosmic::Action::App(Message::PostCompleted(results, queued, history_id))
+        })
+    }
+    
+    fn reference(&self) -> Option<PostReference> {
+        if self.reference_url.trim().is_empty() {
+            return None;
+        }
+        let kind = if self.quote_reference { ReferenceKind::Quote } else { ReferenceKind::Reply };
+        Some(PostReference::new(kind, &self.reference_url))
+    }
+    
+    fn thread_options(&self) -> Option<ThreadOptions> {
+        self.post_as_thread.then_some(ThreadOptions {
+            numbered: self.number_thread_parts,
+        })
+    }
+    
+    /// Delete a history entry's posts from every platform that still has them
+    fn delete_everywhere(&mut self, id: String) -> Task<cosmic::Action<Message>> {
+        let Some(entry) = self.history_entries.iter().find(|entry| entry.id == id) else {
+            return Task::none();
+        };
+        let targets: Vec<(PlatformId, RemoteId)> = entry
+            .targets
+            .iter()
+            .filter(|target| target.is_deletable())
+            .flat_map(|target| {
+                // Replies first, so a thread is never left without its start
+                target
+                    .thread_ids
+                    .iter()
+                    .rev()
+                    .chain(target.remote_id.as_ref())
+                    .map(|remote_id| (target.platform, remote_id.clone()))
+            })
+            .collect();
+        if targets.is_empty() {
+            return Task::none();
+        }
+        
+        self.deleting = Some(id.clone());
+        self.status_message = "Deleting...".to_string();
+        
+        let credentials = self.credentials.clone();
+        let timeouts = PlatformTimeouts::from(&self.config);
+        
+        Task::future(async move {
+            let manager = PostManager::new(credentials).with_timeouts(timeouts);
+            let results = manager.delete(targets).await;
+            cosmic::Action::App(Message::DeleteCompleted(id, results))
+        })
+    }
+    
+    /// Apply the composer's text to the post being edited
+    fn save_edit(&mut self) -> Task<cosmic::Action<Message>> {
+        let Some(editing) = &self.editing else {
+            return Task::none();
+        };
+        let id = editing.entry.id.clone();
+        let targets: Vec<HistoryTarget> = editing
+            .entry
+            .targets
+            .iter()
+            .filter(|target| target.is_deletable())
+            .cloned()
+            .collect();
+        
+        // Edits go out as a single post, so they have to fit in one
+        let too_long: Vec<&str> = targets
+            .iter()
+            .filter(|target| {
+                thread::limit(target.platform)
+                    .is_some_and(|limit| thread::length(target.platform, &self.post_text) > limit)
+            })
+            .map(|target| target.platform.name())
+            .collect();
+        if !too_long.is_empty() {
+            self.status_message = format!("Too long to edit on {}", too_long.join(", "));
+            return Task::none();
+        }
+        
+        // A repost can't carry over the original images, only new ones
+        if !editing.entry.media_hashes.is_empty() && self.image_paths.is_empty() {
+            let reposted: Vec<&str> = editing
+                .methods
+                .iter()
+                .filter(|(_, method)| *method == EditMethod::Repost)
+                .map(|(platform, _)| platform.name())
+                .collect();
+            if !reposted.is_empty() {
+                self.status_message = format!(
+                    "Attach the images again to repost on {}",
+                    reposted.join(", ")
+                );
+                return Task::none();
+            }
+        }
+        
+        self.posting = true;
+        self.status_message = "Saving edit...".to_string();
+        
+        let text = self.post_text.clone();
+        let selected_images = self.selected_images();
+        let credentials = self.credentials.clone();
+        let timeouts = PlatformTimeouts::from(&self.config);
+        let pow_progress = self.pow_progress.clone();
+        
+        Task::future(async move {
+            let mut images = Vec::new();
+            for (path, alt) in selected_images {
+                match std::fs::read(&path) {
+                    Ok(bytes) => images.push(PostImage::new(bytes, alt)),
+                    Err(e) => eprintln!("[App] Failed to load image {}: {}", path.display(), e),
+                }
+            }
+            
+            let manager = PostManager::with_pow_progress(credentials, pow_progress).with_timeouts(timeouts);
+            let results = manager.edit(text.clone(), images, targets).await;
+            cosmic::Action::App(Message::EditCompleted(id, text, results))
+        })
+    }
+    
+    /// Selected image paths paired with their alt text
+    fn selected_images(&self) -> Vec<(std::path::PathBuf, String)> {
+        self.image_paths
+            .iter()
+            .cloned()
+            .zip(self.image_alts.iter().cloned())
+            .collect()
+    }
+    
+    /// Key doh identifies itself with to remote signers, created once
+    fn ensure_bunker_app_key(&mut self) -> anyhow::Result<String> {
+        if let Some(app_key) = &self.credentials.nostr_bunker_app_key {
+            return Ok(app_key.clone());
+        }
+        let app_key = nostr::generate_app_key()?;
+        self.credentials.nostr_bunker_app_key = Some(app_key.clone());
+        Ok(app_key)
+    }
+    
+    /// Reset the composer after the draft reached every platform
+    fn clear_draft(&mut self) {
+        self.post_text.clear();
+        self.text_editor_content = Content::new();
+        self.image_paths.clear();
+        self.image_alts.clear();
+        self.link_preview = None;
+        self.reference_url.clear();
+        self.quote_reference = false;
+        self.draft_changed();
+    }
+    
+    /// Forget where the draft was delivered once it changes, since it is a
+    /// different post now. Copies already in the outbox stay there.
+    fn draft_changed(&mut self) {
+        self.draft_delivered.clear();
+        self.draft_failed.clear();
+        self.draft_outbox_ids.clear();
+        self.draft_delivery_id = None;
+        self.draft_history_id = None;
+    }
+    
+    /// Remove the draft's queued copies for `platforms` before sending it
+    /// there again, so the background retry can't deliver them a second time.
+    ///
+    /// Returns the platforms to send to, leaving out those whose copy the
+    /// background retry is sending right now.
+    fn drop_queued_draft(&mut self, mut platforms: Vec<PlatformId>) -> Vec<PlatformId> {
+        let Some(outbox) = &self.outbox else {
+            return platforms;
+        };
+        for entry in outbox.list() {
+            if !platforms.contains(&entry.platform) || !self.draft_outbox_ids.contains(&entry.id) {
+                continue;
+            }
+            match outbox.remove(&entry.id) {
+                Ok(()) => self.draft_outbox_ids.retain(|id| *id != entry.id),
+                Err(e) => {
+                    eprintln!("[App] Not sending to {} again: {}", entry.platform, e);
+                    platforms.retain(|platform| *platform != entry.platform);
+                }
+            }
+        }
+        self.reload_outbox();
+        platforms
+    }
+    
+    /// Fetch a preview for the draft's first link once typing settles
+    fn refresh_link_preview(&mut self) -> Task<cosmic::Action<Message>> {
+        let url = link_preview::first_url(&self.post_text).map(str::to_string);
+        if self.link_preview.as_ref().map(|preview| &preview.url) == url.as_ref() {
+            return Task::none();
+        }
+        self.link_preview = None;
+        
+        let Some(url) = url else {
+            return Task::none();
+        };
+        Task::future(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(800)).await;
+            cosmic::Action::App(Message::FetchLinkPreview(url))
+        })
+    }
+    
+    fn reload_outbox(&mut self) {
+        self.outbox_entries = self.outbox.as_ref().map(Outbox::list).unwrap_or_default();
+    }
+    
+    /// Retry due outbox entries in the background
+    fn flush_outbox(&mut self) -> Task<cosmic::Action<Message>> {
+        let now = chrono::Utc::now();
+        if self.flushing_outbox || !self.outbox_entries.iter().any(|entry| entry.is_due(now)) {
+            return Task::none();
+        }
+        let Some(outbox) = self.outbox.clone() else {
+            return Task::none();
+        };
+        
+        self.flushing_outbox = true;
+        let credentials = self.credentials.clone();
+        let timeouts = PlatformTimeouts::from(&self.config);
+        let pow_progress = self.pow_progress.clone();
+        
+        Task::future(async move {
+            let manager = PostManager::with_pow_progress(credentials, pow_progress).with_timeouts(timeouts);
+            let outcomes = manager.flush_outbox(&outbox).await;
+            cosmic::Action::App(Message::OutboxFlushed(outcomes))
+        })
+    }
+    
+    fn view_outbox(&self) -> Element<'_, Message> {
+        let mut column = widget::column()
+            .spacing(6)
+            .push(widget::text::body(format!("Outbox ({})", self.outbox_entries.len())));
+        
+        for entry in &self.outbox_entries {
+            let preview: String = entry.text.chars().take(40).collect();
+            let state = match entry.state {
+                OutboxState::Pending => format!(
+                    "pending, next try {}",
+                    entry.next_attempt_at.with_timezone(&chrono::Local).format("%H:%M")
+                ),
+                OutboxState::Failed if entry.maybe_posted => format!(
+                    "may already be posted, check before retrying: {}",
+                    entry.last_error
+                ),
+                OutboxState::Failed => format!("failed: {}", entry.last_error),
+                OutboxState::Sending => "sending...".to_string(),
+            };
+            
+            column = column.push(
+                widget::row()
+                    .spacing(8)
+                    .push(
+                        widget::column()
+                            .push(widget::text::caption(format!("{}: {}", entry.platform, preview)))
+                            .push(widget::text::caption(state))
+                            .width(cosmic::iced::Length::Fill)
+                    )
+                    .push(
+                        widget::button::icon(widget::icon::from_name("view-refresh-symbolic"))
+                            .on_press(Message::RetryOutboxEntry(entry.id.clone()))
+                    )
+                    .push(
+                        widget::button::icon(widget::icon::from_name("edit-delete-symbolic"))
+                            .on_press(Message::DiscardOutboxEntry(entry.id.clone()))
+                    )
+            );
+        }
+        
+        column.into()
+    }
+    
     fn view_main(&self) -> widget::Column<'_, Message> {
         let char_count = self.post_text.chars().count();
         
@@ -585,6 +1632,14 @@ impl AppModel {
                     .spacing(10)
                     .push(widget::text::body("Post to social media").size(18))
                     .push(widget::horizontal_space())
+                    .push(
+                        widget::button::icon(widget::icon::from_name("document-edit-symbolic"))
+                            .on_press_maybe((!self.posting && self.editing.is_none()).then_some(Message::EditLastPost))
+                    )
+                    .push(
+                        widget::button::icon(widget::icon::from_name("document-open-recent-symbolic"))
+                            .on_press(Message::ShowHistory)
+                    )
                     .push(
                         widget::button::icon(widget::icon::from_name("preferences-system-symbolic"))
                             .on_press(Message::ShowSettings)
@@ -596,6 +1651,7 @@ impl AppModel {
                     .height(100)
                     .on_action(Message::TextEditorAction)
             )
+            .push(self.view_link_preview())
             .push(
                 widget::row()
                     .spacing(10)
@@ -605,6 +1661,14 @@ impl AppModel {
                     )
                     .push(widget::text::caption(format!("{} characters", char_count)))
             )
+            .push(
+                widget::settings::item(
+                    "Post as thread",
+                    widget::toggler(self.post_as_thread).on_toggle(Message::ToggleThread),
+                )
+            )
+            .push(self.view_reference())
+            .push(self.view_length_hints())
             .push(
                 widget::button::text("Add Images")
                     .on_press(Message::SelectImages)
@@ -612,6 +1676,7 @@ impl AppModel {
             .push(
                 widget::text::caption(format!("{} image(s) selected", self.image_paths.len()))
             )
+            .push(self.view_image_alts())
             .push(widget::divider::horizontal::default())
             .push(widget::text::body("Post to:"));
         
@@ -621,7 +1686,7 @@ impl AppModel {
                 widget::settings::item(
                     "X (Twitter) ✓",
                     widget::toggler(self.post_to_x)
-                        .on_toggle(|v| Message::TogglePlatform(PlatformType::X, v)),
+                        .on_toggle(|v| Message::TogglePlatform(PlatformId::X, v)),
                 )
             );
         } else {
@@ -635,7 +1700,7 @@ impl AppModel {
                 widget::settings::item(
                     "BlueSky ✓",
                     widget::toggler(self.post_to_bluesky)
-                        .on_toggle(|v| Message::TogglePlatform(PlatformType::BlueSky, v)),
+                        .on_toggle(|v| Message::TogglePlatform(PlatformId::BlueSky, v)),
                 )
             );
         } else {
@@ -649,7 +1714,7 @@ impl AppModel {
                 widget::settings::item(
                     "Nostr ✓",
                     widget::toggler(self.post_to_nostr)
-                        .on_toggle(|v| Message::TogglePlatform(PlatformType::Nostr, v)),
+                        .on_toggle(|v| Message::TogglePlatform(PlatformId::Nostr, v)),
                 )
             );
         } else {
@@ -663,7 +1728,7 @@ impl AppModel {
                 widget::settings::item(
                     "Mastodon ✓",
                     widget::toggler(self.post_to_mastodon)
-                        .on_toggle(|v| Message::TogglePlatform(PlatformType::Mastodon, v)),
+                        .on_toggle(|v| Message::TogglePlatform(PlatformId::Mastodon, v)),
                 )
             );
         } else {
@@ -674,8 +1739,75 @@ impl AppModel {
         
         content_list = content_list.push(widget::divider::horizontal::default());
         
-        let button_element: Element<'_, Message> = if self.posting {
+        let button_element: Element<'_, Message> = if let Some((difficulty, attempts)) = self.pow_progress.mining() {
+            widget::row()
+                .spacing(10)
+                .push(widget::text::body(format!(
+                    "Proof of work (difficulty {}): {} hashes, about {} expected",
+                    difficulty,
+                    attempts,
+                    1u64 << difficulty.min(63)
+                )))
+                .push(
+                    widget::button::standard("Cancel")
+                        .on_press(Message::CancelPow)
+                )
+                .into()
+        } else if self.posting {
             widget::button::text("Posting...").into()
+        } else if let Some(editing) = &self.editing {
+            let names = |method: EditMethod| -> Vec<&str> {
+                editing
+                    .methods
+                    .iter()
+                    .filter(|(_, m)| *m == method)
+                    .map(|(platform, _)| platform.name())
+                    .collect()
+            };
+            let (in_place, reposted) = (names(EditMethod::InPlace), names(EditMethod::Repost));
+            
+            let mut column = widget::column()
+                .spacing(6)
+                .push(widget::text::body(format!(
+                    "Editing post from {}",
+                    editing.entry.posted_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+                )));
+            if !in_place.is_empty() {
+                column = column.push(widget::text::caption(format!("Edited in place: {}", in_place.join(", "))));
+            }
+            if !reposted.is_empty() {
+                column = column.push(widget::text::caption(format!(
+                    "Can only delete and repost: {} (existing replies and links are lost; attach images again to keep them)",
+                    reposted.join(", ")
+                )));
+            }
+            column
+                .push(
+                    widget::row()
+                        .spacing(10)
+                        .push(
+                            widget::button::suggested("Save edit")
+                                .on_press(Message::SaveEdit)
+                        )
+                        .push(
+                            widget::button::standard("Cancel")
+                                .on_press(Message::CancelEdit)
+                        )
+                )
+                .into()
+        } else if !self.draft_failed.is_empty() {
+            let failed: Vec<&str> = self.draft_failed.iter().map(PlatformId::name).collect();
+            widget::row()
+                .spacing(10)
+                .push(
+                    widget::button::suggested(format!("Retry failed ({})", failed.join(", ")))
+                        .on_press(Message::RetryFailedClicked)
+                )
+                .push(
+                    widget::button::standard("Post")
+                        .on_press(Message::PostClicked)
+                )
+                .into()
         } else {
             widget::button::suggested("Post")
                 .on_press(Message::PostClicked)
@@ -688,6 +1820,12 @@ impl AppModel {
             content_list = content_list.push(widget::text::caption(&self.status_message));
         }
         
+        if !self.outbox_entries.is_empty() {
+            content_list = content_list
+                .push(widget::divider::horizontal::default())
+                .push(self.view_outbox());
+        }
+        
         // Add emoji picker modal if visible
         if self.show_emoji_picker {
             content_list = content_list.push(self.view_emoji_picker());
@@ -696,6 +1834,129 @@ impl AppModel {
         content_list
     }
     
+    /// Reply/quote URL field
+    fn view_reference(&self) -> widget::Column<'_, Message> {
+        let mut column = widget::column()
+            .spacing(4)
+            .push(
+                widget::text_input("Reply to / quote (paste a post URL)", &self.reference_url)
+                    .on_input(Message::ReferenceUrlChanged)
+            );
+        
+        let Some(reference) = self.reference() else {
+            return column;
+        };
+        column = column.push(
+            widget::settings::item(
+                "Quote instead of reply",
+                widget::toggler(self.quote_reference).on_toggle(Message::ToggleQuote),
+            )
+        );
+        let hint = match reference.platform() {
+            Some(platform) => format!(
+                "{} {} post. Other platforms get the link.",
+                if self.quote_reference { "Quoting a" } else { "Replying to a" },
+                platform
+            ),
+            None => "Not a recognised post URL. It will be added as a link.".to_string(),
+        };
+        column.push(widget::text::caption(hint))
+    }
+    
+    /// Alt text field for each selected image
+    fn view_image_alts(&self) -> widget::Column<'_, Message> {
+        let mut column = widget::column().spacing(4);
+        for (index, (path, alt)) in self.image_paths.iter().zip(&self.image_alts).enumerate() {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string());
+            column = column
+                .push(widget::text::caption(name))
+                .push(
+                    widget::text_input("Alt text", alt)
+                        .on_input(move |alt| Message::ImageAltChanged(index, alt))
+                );
+        }
+        column
+    }
+    
+    /// Card for the draft's first link, as BlueSky will show it
+    fn view_link_preview(&self) -> widget::Column<'_, Message> {
+        let mut column = widget::column().spacing(2);
+        let Some(preview) = &self.link_preview else {
+            return column;
+        };
+        
+        if !preview.title.is_empty() {
+            column = column.push(widget::text::body(&preview.title));
+        }
+        if !preview.description.is_empty() {
+            let description: String = preview.description.chars().take(140).collect();
+            column = column.push(widget::text::caption(description));
+        }
+        let domain = reqwest::Url::parse(&preview.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| preview.url.clone());
+        column.push(widget::text::caption(domain))
+    }
+    
+    /// Per-platform thread sizes, or a warning when the text is too long
+    fn view_length_hints(&self) -> widget::Column<'_, Message> {
+        let mut column = widget::column().spacing(4);
+        if self.post_as_thread {
+            column = column.push(
+                widget::settings::item(
+                    "Number parts (1/3)",
+                    widget::toggler(self.number_thread_parts).on_toggle(Message::ToggleThreadNumbering),
+                )
+            );
+        }
+        if self.post_text.is_empty() {
+            return column;
+        }
+        
+        let mut hints = Vec::new();
+        for platform in self.selected_platforms() {
+            if let Some(options) = self.thread_options() {
+                let parts = thread::split(platform, &self.post_text, options).len();
+                if parts > 1 {
+                    hints.push(format!("{}: {} parts", platform, parts));
+                }
+            } else if let Some(limit) = thread::limit(platform) {
+                let length = thread::length(platform, &self.post_text);
+                if length > limit {
+                    hints.push(format!("Too long for {} ({}/{})", platform, length, limit));
+                }
+            }
+        }
+        if !hints.is_empty() {
+            column = column.push(widget::text::caption(hints.join(" · ")));
+        }
+        column
+    }
+    
+    /// Browser sign-in button, or the signed-in state with a way out
+    fn view_sign_in(&self, platform: PlatformId, signed_in: bool) -> widget::Row<'_, Message> {
+        let row = widget::row().spacing(8);
+        if signed_in {
+            return row
+                .push(widget::text::caption("Signed in ✓"))
+                .push(widget::button::text("Sign out").on_press(Message::SignOut(platform)));
+        }
+        
+        let label = if self.signing_in == Some(platform) {
+            "Waiting for browser…".to_string()
+        } else {
+            format!("Sign in with {}", platform)
+        };
+        row.push(
+            widget::button::standard(label)
+                .on_press_maybe(self.signing_in.is_none().then_some(Message::SignIn(platform)))
+        )
+    }
+    
     fn view_emoji_picker(&self) -> Element<'_, Message> {
         // Create a grid of emoji buttons
         let emojis_list = emojis::Group::SmileysAndEmotion.emojis()
@@ -749,6 +2010,103 @@ impl AppModel {
         .into()
     }
     
+    fn view_history(&self) -> widget::Column<'_, Message> {
+        let mut content = widget::column()
+            .padding(10)
+            .spacing(10)
+            .push(
+                widget::row()
+                    .spacing(10)
+                    .push(
+                        widget::button::icon(widget::icon::from_name("go-previous-symbolic"))
+                            .on_press(Message::ShowMain)
+                    )
+                    .push(widget::text::body("History").size(18))
+            )
+            .push(widget::divider::horizontal::default());
+        
+        if self.history_entries.is_empty() {
+            return content.push(widget::text::caption("Nothing posted yet"));
+        }
+        
+        let mut list = widget::column().spacing(10);
+        for entry in &self.history_entries {
+            let preview: String = entry.text.chars().take(80).collect();
+            let mut item = widget::column()
+                .spacing(4)
+                .push(widget::text::caption(
+                    entry.posted_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string()
+                ))
+                .push(widget::text::body(preview));
+            
+            if entry.targets.is_empty() {
+                item = item.push(widget::text::caption("Not delivered yet"));
+            }
+            
+            for target in &entry.targets {
+                let mut row = widget::row()
+                    .spacing(8)
+                    .push(widget::text::caption(target.platform.name()))
+                    .push(widget::horizontal_space());
+                
+                if target.deleted_at.is_some() {
+                    row = row.push(widget::text::caption("Deleted"));
+                } else if let Some(url) = &target.url {
+                    row = row
+                        .push(
+                            widget::button::text("Open")
+                                .on_press(Message::OpenUrl(url.clone()))
+                        )
+                        .push(
+                            widget::button::text("Copy")
+                                .on_press(Message::CopyToClipboard(url.clone()))
+                        );
+                } else {
+                    row = row.push(widget::text::caption("No link available"));
+                }
+                
+                item = item.push(row);
+            }
+            
+            if entry.targets.iter().any(|target| target.is_deletable()) {
+                let actions = if self.deleting.as_ref() == Some(&entry.id) {
+                    widget::row().push(widget::text::caption("Deleting..."))
+                } else if self.confirm_delete.as_ref() == Some(&entry.id) {
+                    widget::row()
+                        .spacing(8)
+                        .push(widget::text::caption("Delete this post from every platform?"))
+                        .push(widget::horizontal_space())
+                        .push(
+                            widget::button::destructive("Delete")
+                                .on_press(Message::ConfirmDelete(entry.id.clone()))
+                        )
+                        .push(
+                            widget::button::standard("Cancel")
+                                .on_press(Message::CancelDelete)
+                        )
+                } else {
+                    widget::row()
+                        .push(widget::horizontal_space())
+                        .push(
+                            widget::button::text("Delete everywhere")
+                                .on_press_maybe(self.deleting.is_none().then(|| Message::DeleteEverywhere(entry.id.clone())))
+                        )
+                };
+                item = item.push(actions);
+            }
+            
+            list = list.push(item).push(widget::divider::horizontal::default());
+        }
+        
+        content = content.push(widget::scrollable(list).height(500));
+        
+        if !self.status_message.is_empty() {
+            content = content.push(widget::text::caption(&self.status_message));
+        }
+        
+        content
+    }
+    
     fn view_settings(&self) -> widget::Column<'_, Message> {
         let mut content = widget::column()
             .padding(10)
@@ -777,6 +2135,16 @@ impl AppModel {
         
         if self.twitter_section_expanded {
             content = content
+                .push(
+                    widget::text_input("OAuth 2.0 Client ID", &self.twitter_client_id)
+                        .on_input(Message::TwitterClientIdChanged)
+                )
+                .push(widget::text::caption(format!(
+                    "Create a Native App at developer.x.com with callback http://127.0.0.1:{}/callback",
+                    oauth::x::REDIRECT_PORT
+                )))
+                .push(self.view_sign_in(PlatformId::X, self.credentials.twitter_oauth.is_some()))
+                .push(widget::text::caption("Or use OAuth 1.0a keys:"))
                 .push(
                     widget::text_input("Consumer Key", &self.twitter_consumer_key)
                         .on_input(Message::TwitterConsumerKeyChanged)
@@ -816,12 +2184,24 @@ impl AppModel {
                     widget::text_input("Handle (e.g., user.bsky.social)", &self.bluesky_handle)
                         .on_input(Message::BlueSkyHandleChanged)
                 )
+                .push(self.view_sign_in(PlatformId::BlueSky, self.credentials.bluesky_oauth.is_some()))
+                .push(widget::text::caption("Or use an app password:"))
                 .push(
                     widget::text_input("App Password", &self.bluesky_password)
                         .on_input(Message::BlueSkyPasswordChanged)
                         .password()
                 )
-                .push(widget::text::caption("Create app password at bsky.app/settings/app-passwords"));
+                .push(widget::text::caption("Create app password at bsky.app/settings/app-passwords"))
+                .push(
+                    widget::text_input("PDS URL", &self.bluesky_service_url)
+                        .on_input(Message::BlueSkyServiceUrlChanged)
+                )
+                .push(widget::text::caption("Leave empty to find your PDS from your handle"))
+                .push(
+                    widget::text_input("AppView URL", &self.bluesky_appview_url)
+                        .on_input(Message::BlueSkyAppViewUrlChanged)
+                )
+                .push(widget::text::caption("Used to look up posts you reply to or quote. Leave empty for public.api.bsky.app"));
         }
         content = content.push(widget::divider::horizontal::default());
         
@@ -852,7 +2232,22 @@ impl AppModel {
                             .on_input(Message::NostrNsecChanged)
                             .password()
                     )
-                    .push(widget::text::caption("Your private Nostr key (nsec1...)"));
+                    .push(widget::text::caption("Your private Nostr key (nsec1...)"))
+                    .push(
+                        widget::text_input("Remote signer (bunker://...)", &self.nostr_bunker_uri)
+                            .on_input(Message::NostrBunkerUriChanged)
+                    )
+                    .push(
+                        widget::row()
+                            .spacing(8)
+                            .push(widget::text::caption(
+                                "Paste a bunker:// URI from your signer, or create a link and paste it into your signer. Used instead of the nsec key.",
+                            ))
+                            .push(
+                                widget::button::text("Create nostrconnect:// link")
+                                    .on_press(Message::NostrCreateConnectUri)
+                            )
+                    );
             }
             
             content = content
@@ -860,12 +2255,40 @@ impl AppModel {
                     widget::text_input("Relays (comma-separated)", &self.nostr_relays)
                         .on_input(Message::NostrRelaysChanged)
                 )
-                .push(widget::text::caption("Leave empty for defaults: relay.primal.net, relay.damus.io, relay.pleb.one"))
+                .push(widget::text::caption(
+                    "Used to find relay lists, and to post to when your account has none. \
+                     Leave empty for defaults: relay.primal.net, relay.damus.io, relay.pleb.one"
+                ))
+                .push(
+                    widget::button::text("Publish relay list (NIP-65)")
+                        .on_press(Message::NostrPublishRelayList)
+                )
+                .push(widget::text::caption("Save first. Clients then find your posts on these relays."))
                 .push(
                     widget::text_input("Blossom Server URL", &self.nostr_blossom_server)
                         .on_input(Message::NostrBlossomServerChanged)
                 )
-                .push(widget::text::caption("URL for uploading images (e.g., https://blossom.primal.net)"));
+                .push(widget::text::caption("URL for uploading images (e.g., https://blossom.primal.net)"))
+                .push(
+                    widget::settings::item(
+                        "Publish image metadata (NIP-94)",
+                        widget::toggler(self.nostr_file_metadata)
+                            .on_toggle(Message::NostrToggleFileMetadata),
+                    )
+                )
+                .push(
+                    widget::text_input("Proof of work difficulty", &self.nostr_pow_difficulty)
+                        .on_input(Message::NostrPowDifficultyChanged)
+                )
+                .push(widget::text::caption(
+                    "Leading zero bits mined into each note (NIP-13), for relays that require it. \
+                     Each extra bit doubles the time. Leave empty for none."
+                ))
+                .push(
+                    widget::text_input("Web Viewer URL", &self.nostr_viewer_url)
+                        .on_input(Message::NostrViewerUrlChanged)
+                )
+                .push(widget::text::caption("Used for post links. Leave empty for https://njump.me"));
         }
         content = content.push(widget::divider::horizontal::default());
         
@@ -886,6 +2309,8 @@ impl AppModel {
                     widget::text_input("Instance URL (e.g., https://mastodon.social)", &self.mastodon_instance_url)
                         .on_input(Message::MastodonInstanceUrlChanged)
                 )
+                .push(self.view_sign_in(PlatformId::Mastodon, self.credentials.mastodon_access_token.is_some()))
+                .push(widget::text::caption("Or paste an access token:"))
                 .push(
                     widget::text_input("Access Token", &self.mastodon_access_token)
                         .on_input(Message::MastodonAccessTokenChanged)
@@ -1,6 +1,16 @@
 // SPDX-License-Identifier: MIT
 
 use cosmic::cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use std::path::PathBuf;
+
+/// Directory for application data (`$XDG_DATA_HOME/doh`, usually `~/.local/share/doh`)
+pub fn data_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(base.join("doh"))
+}
 
 #[derive(Debug, Clone, CosmicConfigEntry, Eq, PartialEq)]
 #[version = 1]
@@ -9,6 +19,11 @@ pub struct Config {
     pub post_to_bluesky: bool,
     pub post_to_nostr: bool,
     pub post_to_mastodon: bool,
+    /// Per-platform posting timeouts, in seconds
+    pub x_timeout_secs: u64,
+    pub bluesky_timeout_secs: u64,
+    pub nostr_timeout_secs: u64,
+    pub mastodon_timeout_secs: u64,
 }
 
 impl Default for Config {
@@ -18,6 +33,10 @@ impl Default for Config {
             post_to_bluesky: false,
             post_to_nostr: false,
             post_to_mastodon: false,
+            x_timeout_secs: 60,
+            bluesky_timeout_secs: 60,
+            nostr_timeout_secs: 30,
+            mastodon_timeout_secs: 60,
         }
     }
 }
@@ -2,6 +2,8 @@
 
 //! Secure credential storage using system keyring
 
+use crate::oauth::OAuthTokens;
+use crate::oauth::atproto::AtprotoSession;
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
 
@@ -14,20 +16,48 @@ pub struct Credentials {
     pub twitter_consumer_secret: Option<String>,
     pub twitter_access_token: Option<String>,
     pub twitter_access_secret: Option<String>,
+    // X/Twitter OAuth 2.0, used instead of the keys above once signed in
+    pub twitter_client_id: Option<String>,
+    pub twitter_oauth: Option<OAuthTokens>,
     
     // BlueSky
     pub bluesky_handle: Option<String>,
     pub bluesky_app_password: Option<String>,
+    /// PDS to use instead of the one found in the account's DID document
+    pub bluesky_service_url: Option<String>,
+    /// AppView to look up posts on instead of the public Bluesky one
+    #[serde(default)]
+    pub bluesky_appview_url: Option<String>,
+    /// Browser sign-in, used instead of the app password
+    pub bluesky_oauth: Option<AtprotoSession>,
     
     // Nostr
     pub nostr_nsec: Option<String>,
     pub nostr_use_pleb_signer: bool,
+    /// NIP-46 remote signer (`bunker://` or `nostrconnect://`), used instead
+    /// of the nsec key
+    #[serde(default)]
+    pub nostr_bunker_uri: Option<String>,
+    /// Key doh uses to talk to the remote signer
+    #[serde(default)]
+    pub nostr_bunker_app_key: Option<String>,
     pub nostr_image_host_url: Option<String>,
     pub nostr_relays: Vec<String>,
+    /// Web viewer used to link to published events, e.g. https://njump.me
+    pub nostr_viewer_url: Option<String>,
+    /// Publish a NIP-94 file metadata event for each uploaded image
+    #[serde(default)]
+    pub nostr_file_metadata: bool,
+    /// Leading zero bits to mine into each note's id (NIP-13), 0 for none
+    #[serde(default)]
+    pub nostr_pow_difficulty: u8,
     
     // Mastodon
     pub mastodon_instance_url: Option<String>,
     pub mastodon_access_token: Option<String>,
+    /// App registered by the browser sign-in
+    pub mastodon_client_id: Option<String>,
+    pub mastodon_client_secret: Option<String>,
 }
 
 impl Credentials {
@@ -36,8 +66,11 @@ impl Credentials {
         let entry = keyring::Entry::new(SERVICE_NAME, "credentials")?;
         match entry.get_password() {
             Ok(json) => {
-                eprintln!("[Credentials] Loaded from keyring: {}", json);
-                serde_json::from_str(&json).map_err(|e| anyhow!("Failed to parse credentials: {}", e))
+                eprintln!("[Credentials] Loaded from keyring");
+                let mut credentials: Self = serde_json::from_str(&json)
+                    .map_err(|e| anyhow!("Failed to parse credentials: {}", e))?;
+                credentials.normalize();
+                Ok(credentials)
             }
             Err(keyring::Error::NoEntry) => {
                 eprintln!("[Credentials] No entry found in keyring, using defaults");
@@ -52,8 +85,10 @@ impl Credentials {
     
     /// Save credentials to system keyring
     pub fn save(&self) -> Result<()> {
-        let json = serde_json::to_string(self)?;
-        eprintln!("[Credentials] Saving to keyring: {}", json);
+        let mut credentials = self.clone();
+        credentials.normalize();
+        let json = serde_json::to_string(&credentials)?;
+        eprintln!("[Credentials] Saving to keyring");
         let entry = keyring::Entry::new(SERVICE_NAME, "credentials")?;
         entry.set_password(&json)
             .map_err(|e| {
@@ -64,22 +99,50 @@ impl Credentials {
         Ok(())
     }
     
+    /// Apply a change to the stored credentials, such as refreshed OAuth
+    /// tokens, leaving everything else as it is in the keyring
+    pub fn update(change: impl FnOnce(&mut Self)) -> Result<()> {
+        let mut credentials = Self::load()?;
+        change(&mut credentials);
+        credentials.save()
+    }
+    
+    /// Treat blank optional settings as not set
+    fn normalize(&mut self) {
+        self.nostr_bunker_uri = self
+            .nostr_bunker_uri
+            .take()
+            .map(|uri| uri.trim().to_string())
+            .filter(|uri| !uri.is_empty());
+    }
+    
+    /// Take OAuth tokens from `stored`, which may have been refreshed since
+    /// this copy was loaded
+    pub fn keep_tokens_from(&mut self, stored: &Self) {
+        self.twitter_oauth = stored.twitter_oauth.clone();
+        self.bluesky_oauth = stored.bluesky_oauth.clone();
+    }
+    
     /// Check if X/Twitter is configured
     pub fn has_twitter(&self) -> bool {
-        self.twitter_consumer_key.is_some()
+        let oauth2 = self.twitter_client_id.is_some() && self.twitter_oauth.is_some();
+        let oauth1 = self.twitter_consumer_key.is_some()
             && self.twitter_consumer_secret.is_some()
             && self.twitter_access_token.is_some()
-            && self.twitter_access_secret.is_some()
+            && self.twitter_access_secret.is_some();
+        oauth2 || oauth1
     }
     
     /// Check if BlueSky is configured
     pub fn has_bluesky(&self) -> bool {
-        self.bluesky_handle.is_some() && self.bluesky_app_password.is_some()
+        self.bluesky_oauth.is_some()
+            || (self.bluesky_handle.is_some() && self.bluesky_app_password.is_some())
     }
     
     /// Check if Nostr is configured
     pub fn has_nostr(&self) -> bool {
-        self.nostr_use_pleb_signer || self.nostr_nsec.is_some()
+        let bunker = self.nostr_bunker_uri.as_deref().is_some_and(|uri| !uri.trim().is_empty());
+        self.nostr_use_pleb_signer || self.nostr_nsec.is_some() || bunker
     }
     
     /// Check if Mastodon is configured
@@ -87,3 +150,81 @@ impl Credentials {
         self.mastodon_instance_url.is_some() && self.mastodon_access_token.is_some()
     }
 }
+
+/// A logged-in BlueSky session. It lives in its own keyring entry so that
+/// saving the settings never overwrites tokens refreshed in the meantime.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlueSkySession {
+    /// Handle the session was created for
+    pub handle: String,
+    pub did: String,
+    /// PDS that issued the tokens
+    pub service_url: String,
+    pub access_jwt: String,
+    pub refresh_jwt: String,
+}
+
+impl BlueSkySession {
+    const ENTRY: &'static str = "bluesky-session";
+    
+    /// Load the stored session, if any
+    pub fn load() -> Option<Self> {
+        let entry = keyring::Entry::new(SERVICE_NAME, Self::ENTRY).ok()?;
+        match entry.get_password() {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| eprintln!("[Credentials] Ignoring unreadable BlueSky session: {}", e))
+                .ok(),
+            Err(keyring::Error::NoEntry) => None,
+            Err(e) => {
+                eprintln!("[Credentials] Error loading BlueSky session: {}", e);
+                None
+            }
+        }
+    }
+    
+    /// Store the session, replacing any previous one
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        let entry = keyring::Entry::new(SERVICE_NAME, Self::ENTRY)?;
+        entry.set_password(&json)
+            .map_err(|e| anyhow!("Failed to save BlueSky session: {}", e))
+    }
+    
+    /// Forget the stored session
+    pub fn clear() {
+        let result = keyring::Entry::new(SERVICE_NAME, Self::ENTRY).and_then(|entry| entry.delete_credential());
+        match result {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => eprintln!("[Credentials] Failed to clear BlueSky session: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_bunker_uri_is_not_configured() {
+        let mut credentials = Credentials {
+            nostr_bunker_uri: Some("  ".to_string()),
+            ..Credentials::default()
+        };
+        assert!(!credentials.has_nostr());
+
+        credentials.normalize();
+        assert_eq!(credentials.nostr_bunker_uri, None);
+    }
+
+    #[test]
+    fn bunker_uri_is_trimmed() {
+        let mut credentials = Credentials {
+            nostr_bunker_uri: Some(" bunker://abc \n".to_string()),
+            ..Credentials::default()
+        };
+        assert!(credentials.has_nostr());
+
+        credentials.normalize();
+        assert_eq!(credentials.nostr_bunker_uri.as_deref(), Some("bunker://abc"));
+    }
+}
@@ -0,0 +1,213 @@
+// SPDX-License-Identifier: MIT
+
+//! Local log of published posts, stored as JSON lines
+
+use crate::platforms::{PlatformId, PostImage, PostOutcome, RemoteId};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Held for every write, so an append can't land between another write's
+/// read and its rewrite of the file
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Where a post ended up on one platform
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryTarget {
+    pub platform: PlatformId,
+    pub url: Option<String>,
+    pub remote_id: Option<RemoteId>,
+    /// Ids of the follow-up posts if the text was posted as a thread
+    #[serde(default)]
+    pub thread_ids: Vec<RemoteId>,
+    /// Ids of the attached media, kept when the post is edited in place
+    #[serde(default)]
+    pub media_ids: Vec<String>,
+    /// When the post was deleted from this platform
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl HistoryTarget {
+    pub fn from_outcome(outcome: &PostOutcome) -> Self {
+        Self {
+            platform: outcome.platform,
+            url: outcome.url.clone(),
+            remote_id: outcome.remote_id.clone(),
+            thread_ids: outcome.thread_ids.clone(),
+            media_ids: outcome.media_ids.clone(),
+            deleted_at: None,
+        }
+    }
+    
+    /// Whether the post is still up and can be deleted
+    pub fn is_deletable(&self) -> bool {
+        self.deleted_at.is_none() && self.remote_id.is_some()
+    }
+}
+
+/// A published post
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub posted_at: DateTime<Utc>,
+    pub text: String,
+    /// SHA-256 of each attached image, as sent
+    pub media_hashes: Vec<String>,
+    /// Platforms the post was delivered to
+    pub targets: Vec<HistoryTarget>,
+    /// When the text was last edited
+    #[serde(default)]
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+/// On-disk post history
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// Open the history under the XDG data directory
+    pub fn open_default() -> Result<Self> {
+        let dir = crate::config::data_dir().ok_or_else(|| anyhow!("No data directory available"))?;
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self::open(dir.join("history.jsonl")))
+    }
+
+    pub fn open(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// All entries, newest first. Unreadable lines are skipped.
+    pub fn list(&self) -> Vec<HistoryEntry> {
+        let Ok(contents) = std::fs::read_to_string(&self.path) else {
+            return Vec::new();
+        };
+
+        let mut entries: Vec<HistoryEntry> = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    eprintln!("[History] Skipping unreadable entry: {}", e);
+                    None
+                }
+            })
+            .collect();
+        entries.reverse();
+        entries
+    }
+
+    /// Record a post and the platforms it reached, including threads that
+    /// were only partly published
+    pub fn record(&self, text: &str, images: &[PostImage], outcomes: &[PostOutcome]) -> Result<HistoryEntry> {
+        use sha2::{Digest, Sha256};
+
+        let posted_at = Utc::now();
+        let entry = HistoryEntry {
+            id: posted_at.format("%Y%m%dT%H%M%S%.6f").to_string(),
+            posted_at,
+            text: text.to_string(),
+            media_hashes: images
+                .iter()
+                .map(|image| format!("{:x}", Sha256::digest(&image.bytes)))
+                .collect(),
+            targets: outcomes
+                .iter()
+                .filter(|outcome| outcome.is_success() || outcome.remote_id.is_some())
+                .map(HistoryTarget::from_outcome)
+                .collect(),
+            edited_at: None,
+        };
+
+        let _lock = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+        Ok(entry)
+    }
+
+    /// Add a late delivery (e.g. from the outbox) to an existing entry
+    pub fn add_target(&self, id: &str, target: HistoryTarget) -> Result<()> {
+        let _lock = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries = self.list();
+        let entry = entries
+            .iter_mut()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| anyhow!("No history entry {}", id))?;
+        entry.targets.retain(|existing| existing.platform != target.platform);
+        entry.targets.push(target);
+
+        entries.reverse();
+        self.rewrite(&entries)
+    }
+
+    /// Record an edit: the new text and the targets that took it, which may
+    /// have moved to new remote ids if they were reposted. Targets edited in
+    /// place keep their thread parts.
+    pub fn record_edit(&self, id: &str, text: &str, outcomes: &[PostOutcome]) -> Result<()> {
+        let _lock = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries = self.list();
+        let entry = entries
+            .iter_mut()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| anyhow!("No history entry {}", id))?;
+        entry.text = text.to_string();
+        entry.edited_at = Some(Utc::now());
+        for outcome in outcomes.iter().filter(|outcome| outcome.is_success() || outcome.partial) {
+            let existing = entry.targets.iter_mut().find(|existing| existing.platform == outcome.platform);
+            match existing {
+                Some(existing) if existing.remote_id == outcome.remote_id => {
+                    existing.url = outcome.url.clone().or(existing.url.take());
+                    existing.media_ids = outcome.media_ids.clone();
+                }
+                _ => {
+                    entry.targets.retain(|existing| existing.platform != outcome.platform);
+                    entry.targets.push(HistoryTarget::from_outcome(outcome));
+                }
+            }
+        }
+
+        entries.reverse();
+        self.rewrite(&entries)
+    }
+
+    /// Mark an entry's posts on the given platforms as deleted
+    pub fn mark_deleted(&self, id: &str, platforms: &[PlatformId]) -> Result<()> {
+        let _lock = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries = self.list();
+        let entry = entries
+            .iter_mut()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| anyhow!("No history entry {}", id))?;
+        let now = Utc::now();
+        for target in entry.targets.iter_mut().filter(|target| platforms.contains(&target.platform)) {
+            target.deleted_at = Some(now);
+        }
+
+        entries.reverse();
+        self.rewrite(&entries)
+    }
+
+    /// Replace the whole log, oldest entry first
+    fn rewrite(&self, entries: &[HistoryEntry]) -> Result<()> {
+        let mut contents = String::new();
+        for entry in entries {
+            contents.push_str(&serde_json::to_string(entry)?);
+            contents.push('\n');
+        }
+
+        let tmp = self.path.with_extension("jsonl.tmp");
+        std::fs::write(&tmp, contents)?;
+        std::fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+}
@@ -114,6 +114,94 @@ pub fn get_mime_type(bytes: &[u8]) -> Result<String> {
     }.to_string())
 }
 
+/// Width and height in pixels, read from the image header
+pub fn dimensions(bytes: &[u8]) -> Result<(u32, u32)> {
+    Ok(image::ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_dimensions()?)
+}
+
+/// Characters of the base 83 encoding blurhashes use
+const BLURHASH_CHARS: &[u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// Blurhash of an image, which clients show as a placeholder while the
+/// image loads (https://blurha.sh)
+pub fn blurhash(bytes: &[u8]) -> Result<String> {
+    // The hash only keeps a few colour components, so a thumbnail is plenty
+    let img = image::load_from_memory(bytes)?.thumbnail(64, 64).to_rgb8();
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return Err(anyhow!("Image is empty"));
+    }
+    
+    // More components along the longer side
+    let (x_components, y_components) = if width >= height { (4, 3) } else { (3, 4) };
+    let mut factors = Vec::with_capacity(x_components * y_components);
+    for j in 0..y_components {
+        for i in 0..x_components {
+            let normalisation = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            let mut factor = [0.0; 3];
+            for (x, y, pixel) in img.enumerate_pixels() {
+                let basis = normalisation
+                    * (std::f64::consts::PI * i as f64 * x as f64 / width as f64).cos()
+                    * (std::f64::consts::PI * j as f64 * y as f64 / height as f64).cos();
+                for (channel, value) in factor.iter_mut().zip(pixel.0) {
+                    *channel += basis * srgb_to_linear(value);
+                }
+            }
+            let scale = 1.0 / (width * height) as f64;
+            factors.push(factor.map(|value| value * scale));
+        }
+    }
+    
+    let mut hash = String::new();
+    encode_base83((x_components - 1 + (y_components - 1) * 9) as u32, 1, &mut hash);
+    
+    let (dc, ac) = factors.split_first().expect("at least one component");
+    let actual_max = ac.iter().flatten().fold(0.0_f64, |max, value| max.max(value.abs()));
+    let quantised_max = ((actual_max * 166.0 - 0.5).floor()).clamp(0.0, 82.0) as u32;
+    let max = (quantised_max + 1) as f64 / 166.0;
+    encode_base83(quantised_max, 1, &mut hash);
+    
+    let [r, g, b] = dc.map(linear_to_srgb);
+    encode_base83((r << 16) + (g << 8) + b, 4, &mut hash);
+    for factor in ac {
+        let [r, g, b] = factor.map(|value| {
+            let scaled = (value / max).abs().sqrt().copysign(value);
+            (scaled * 9.0 + 9.5).floor().clamp(0.0, 18.0) as u32
+        });
+        encode_base83(r * 19 * 19 + g * 19 + b, 2, &mut hash);
+    }
+    
+    Ok(hash)
+}
+
+fn encode_base83(value: u32, length: u32, hash: &mut String) {
+    for i in 1..=length {
+        let digit = (value / 83_u32.pow(length - i)) % 83;
+        hash.push(BLURHASH_CHARS[digit as usize] as char);
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let value = value as f64 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> u32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.003_130_8 {
+        (value * 12.92 * 255.0 + 0.5) as u32
+    } else {
+        ((1.055 * value.powf(1.0 / 2.4) - 0.055) * 255.0 + 0.5) as u32
+    }
+}
+
 /// Process image for platform requirements
 pub struct ImageProcessor {
     max_size: Option<usize>,
@@ -0,0 +1,357 @@
+// SPDX-License-Identifier: MIT
+
+//! OpenGraph metadata for link cards and the composer preview
+
+use anyhow::{Result, anyhow};
+use std::time::Duration;
+
+/// Largest page we read looking for metadata; tags live in `<head>`
+const MAX_HTML_BYTES: usize = 512 * 1024;
+
+/// Largest preview image we download
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Title, description and image of a web page
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: String,
+    pub description: String,
+    /// Absolute URL of the preview image
+    pub image_url: Option<String>,
+}
+
+/// Fetches link previews over HTTP(S)
+#[derive(Debug, Clone)]
+pub struct LinkPreviewFetcher {
+    client: reqwest::Client,
+}
+
+impl Default for LinkPreviewFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinkPreviewFetcher {
+    pub fn new() -> Self {
+        Self::with_timeout(Duration::from_secs(10))
+    }
+
+    pub fn with_timeout(timeout: Duration) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .user_agent(concat!("doh/", env!("CARGO_PKG_VERSION"), " (link preview)"))
+            .build()
+            .unwrap_or_default();
+        Self { client }
+    }
+
+    /// Fetch a page and read its OpenGraph (or plain HTML) metadata
+    pub async fn fetch(&self, url: &str) -> Result<LinkPreview> {
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("{} returned {}", url, response.status()));
+        }
+
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_none_or(|value| value.contains("html"));
+        if !is_html {
+            return Err(anyhow!("{} is not an HTML page", url));
+        }
+
+        // Redirects may have moved us, and relative image URLs follow the final page
+        let final_url = response.url().clone();
+        let html = read_limited(response, MAX_HTML_BYTES).await?;
+        let html = String::from_utf8_lossy(&html);
+
+        let mut preview = parse_html(&html, &final_url);
+        preview.url = url.to_string();
+        Ok(preview)
+    }
+
+    /// Download a preview's image
+    pub async fn fetch_image(&self, preview: &LinkPreview) -> Result<Vec<u8>> {
+        let image_url = preview.image_url.as_deref().ok_or_else(|| anyhow!("Preview has no image"))?;
+        let response = self.client.get(image_url).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("{} returned {}", image_url, response.status()));
+        }
+        read_limited(response, MAX_IMAGE_BYTES).await
+    }
+}
+
+/// Read a response body, stopping at `limit` bytes
+async fn read_limited(mut response: reqwest::Response, limit: usize) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= limit {
+            body.truncate(limit);
+            break;
+        }
+    }
+    Ok(body)
+}
+
+/// First http(s) link in a text, without trailing punctuation
+pub fn first_url(text: &str) -> Option<&str> {
+    text.split_whitespace()
+        .map(|word| word.trim_start_matches('('))
+        .find(|word| word.starts_with("https://") || word.starts_with("http://"))
+        .map(trim_url)
+        .filter(|url| url.contains('.'))
+}
+
+/// Drop trailing punctuation that is more likely the sentence's than the
+/// URL's. Closing parentheses stay when the URL opened them.
+pub(crate) fn trim_url(url: &str) -> &str {
+    let mut url = url.trim_end_matches(|c: char| ".,;:!?'\"".contains(c));
+    while url.ends_with(')') && url.matches(')').count() > url.matches('(').count() {
+        url = url[..url.len() - 1].trim_end_matches(|c: char| ".,;:!?'\"".contains(c));
+    }
+    url
+}
+
+/// Extract metadata from a page, preferring OpenGraph tags over `<title>`
+/// and `<meta name="description">`
+pub fn parse_html(html: &str, base_url: &reqwest::Url) -> LinkPreview {
+    let mut og_title = None;
+    let mut og_description = None;
+    let mut og_image = None;
+    let mut description = None;
+
+    for tag in html.split("<meta").skip(1) {
+        let Some(tag) = tag.split('>').next() else { continue };
+        let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+        let Some(content) = attribute(tag, "content") else { continue };
+        match key.map(|key| key.to_lowercase()).as_deref() {
+            Some("og:title") => og_title = og_title.or(Some(content)),
+            Some("og:description") => og_description = og_description.or(Some(content)),
+            Some("og:image" | "og:image:url" | "og:image:secure_url") => og_image = og_image.or(Some(content)),
+            Some("description") => description = description.or(Some(content)),
+            _ => {}
+        }
+    }
+
+    let title = og_title.or_else(|| {
+        let lower = html.to_ascii_lowercase();
+        let start = lower.find("<title")?;
+        let start = start + lower[start..].find('>')? + 1;
+        let end = start + lower[start..].find("</title")?;
+        Some(decode_entities(html[start..end].trim()))
+    });
+
+    LinkPreview {
+        url: base_url.to_string(),
+        title: title.unwrap_or_default(),
+        description: og_description.or(description).unwrap_or_default(),
+        image_url: og_image
+            .and_then(|image| base_url.join(&image).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .map(String::from),
+    }
+}
+
+/// Value of an attribute in the inside of an HTML tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search = 0;
+    while let Some(found) = lower[search..].find(name) {
+        let start = search + found;
+        search = start + name.len();
+
+        // Must be a whole attribute name followed by `=`
+        let preceded = lower[..start].chars().next_back().is_none_or(char::is_whitespace);
+        let rest = lower[search..].trim_start();
+        if !preceded || !rest.starts_with('=') {
+            continue;
+        }
+
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let value = match value.chars().next()? {
+            quote @ ('"' | '\'') => value[1..].split(quote).next()?,
+            _ => value.split(|c: char| c.is_whitespace() || c == '/').next()?,
+        };
+        return Some(decode_entities(value.trim()));
+    }
+    None
+}
+
+/// Decode the HTML entities that commonly appear in metadata
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let replacement = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+
+        match (entity, replacement) {
+            (Some(entity), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serve a page with a preview image, a JSON file, an oversized image
+    /// and a path that never answers. Returns the server's base URL.
+    async fn serve() -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buffer = vec![0; 4096];
+                    let read = stream.read(&mut buffer).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buffer[..read]).to_string();
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+                    let (content_type, body) = match path.as_str() {
+                        "/page" => (
+                            "text/html; charset=utf-8",
+                            br#"<html><head><meta property="og:title" content="Local page"><meta property="og:image" content="/image.png"></head></html>"#.to_vec(),
+                        ),
+                        "/image.png" => ("image/png", b"not really a png".to_vec()),
+                        "/huge.png" => ("image/png", vec![0; MAX_IMAGE_BYTES + 4096]),
+                        "/data.json" => ("application/json", b"{}".to_vec()),
+                        _ => {
+                            // Hold the connection open without answering
+                            tokio::time::sleep(Duration::from_secs(30)).await;
+                            return;
+                        }
+                    };
+                    let head = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        content_type,
+                        body.len()
+                    );
+                    let _ = stream.write_all(head.as_bytes()).await;
+                    let _ = stream.write_all(&body).await;
+                });
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn fetches_page_and_image() {
+        let url = serve().await;
+        let fetcher = LinkPreviewFetcher::new();
+        let preview = fetcher.fetch(&format!("{}/page", url)).await.unwrap();
+
+        assert_eq!(preview.title, "Local page");
+        assert_eq!(preview.image_url, Some(format!("{}/image.png", url)));
+        assert_eq!(fetcher.fetch_image(&preview).await.unwrap(), b"not really a png");
+    }
+
+    #[tokio::test]
+    async fn rejects_pages_that_are_not_html() {
+        let url = serve().await;
+        let fetcher = LinkPreviewFetcher::new();
+        assert!(fetcher.fetch(&format!("{}/data.json", url)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn stops_reading_large_images() {
+        let url = serve().await;
+        let preview = LinkPreview {
+            image_url: Some(format!("{}/huge.png", url)),
+            ..LinkPreview::default()
+        };
+        let image = LinkPreviewFetcher::new().fetch_image(&preview).await.unwrap();
+        assert_eq!(image.len(), MAX_IMAGE_BYTES);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_slow_servers() {
+        let url = serve().await;
+        let fetcher = LinkPreviewFetcher::with_timeout(Duration::from_millis(200));
+        let started = std::time::Instant::now();
+
+        assert!(fetcher.fetch(&format!("{}/slow", url)).await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    fn base() -> reqwest::Url {
+        reqwest::Url::parse("https://example.com/blog/post").unwrap()
+    }
+
+    #[test]
+    fn prefers_opengraph() {
+        let html = r#"<html><head>
+            <title>Plain title</title>
+            <meta name="description" content="Plain description">
+            <meta property="og:title" content="OG &amp; title">
+            <meta property="og:description" content='OG description'>
+            <meta property="og:image" content="/img/cover.png">
+        </head></html>"#;
+        let preview = parse_html(html, &base());
+        assert_eq!(preview.url, "https://example.com/blog/post");
+        assert_eq!(preview.title, "OG & title");
+        assert_eq!(preview.description, "OG description");
+        assert_eq!(preview.image_url.as_deref(), Some("https://example.com/img/cover.png"));
+    }
+
+    #[test]
+    fn falls_back_to_title_and_description() {
+        let html = "<TITLE> Caf&#233; &#x2014; menu </TITLE><meta content=\"Open daily\" name=description>";
+        let preview = parse_html(html, &base());
+        assert_eq!(preview.title, "Café — menu");
+        assert_eq!(preview.description, "Open daily");
+        assert_eq!(preview.image_url, None);
+    }
+
+    #[test]
+    fn ignores_non_http_images() {
+        let html = r#"<meta property="og:image" content="data:image/png;base64,AAAA">"#;
+        assert_eq!(parse_html(html, &base()).image_url, None);
+    }
+
+    #[test]
+    fn attribute_names_must_match_whole() {
+        let html = r#"<meta data-name="og:title" name="og:title" data-content="no" content="yes">"#;
+        assert_eq!(parse_html(html, &base()).title, "yes");
+    }
+
+    #[test]
+    fn first_url_trims_punctuation() {
+        assert_eq!(first_url("Look (https://example.com/a_(b)).").unwrap(), "https://example.com/a_(b)");
+        assert_eq!(first_url("no links, just http://x here"), None);
+    }
+}
@@ -3,10 +3,15 @@
 mod app;
 mod config;
 mod credentials;
+mod history;
 mod i18n;
 mod image_utils;
+mod link_preview;
+mod oauth;
+mod outbox;
 mod platforms;
 mod post_manager;
+mod thread;
 
 fn main() -> cosmic::iced::Result {
     // Get the system's preferred languages.
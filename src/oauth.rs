@@ -0,0 +1,282 @@
+// SPDX-License-Identifier: MIT
+
+//! Browser sign-in with OAuth 2.0 authorization codes and PKCE
+//!
+//! The authorization server sends the browser back to a one-shot listener on
+//! 127.0.0.1, so doh needs neither a client secret nor a hosted redirect page.
+
+pub mod atproto;
+mod dpop;
+pub mod mastodon;
+pub mod x;
+
+pub use dpop::{DpopKey, DpopNonce};
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// How long we wait for the user to finish signing in
+const SIGN_IN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Refresh access tokens this many seconds before they expire
+const EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// Tokens issued by an authorization server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuthTokens {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// When the access token stops working, if it expires at all
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl OAuthTokens {
+    /// Whether the access token has expired or is about to
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| {
+            expires_at - chrono::Duration::seconds(EXPIRY_MARGIN_SECS) <= chrono::Utc::now()
+        })
+    }
+}
+
+/// Token endpoint answer (RFC 6749 section 5.1)
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<i64>,
+    /// Account the tokens belong to; AT Protocol servers send the DID
+    #[serde(default)]
+    sub: Option<String>,
+}
+
+impl TokenResponse {
+    /// Tokens to keep. A refresh that doesn't rotate the refresh token
+    /// leaves the previous one valid.
+    fn into_tokens(self, previous_refresh_token: Option<String>) -> OAuthTokens {
+        OAuthTokens {
+            access_token: self.access_token,
+            refresh_token: self.refresh_token.or(previous_refresh_token),
+            expires_at: self
+                .expires_in
+                .map(|seconds| chrono::Utc::now() + chrono::Duration::seconds(seconds)),
+        }
+    }
+}
+
+/// Read a token endpoint response, surfacing the server's error if any
+async fn token_response(response: reqwest::Response) -> Result<TokenResponse> {
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Token request failed: {} - {}", status, error_text));
+    }
+    Ok(response.json().await?)
+}
+
+/// PKCE verifier and its S256 challenge (RFC 7636)
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Default for Pkce {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pkce {
+    pub fn new() -> Self {
+        let verifier = random_string(32);
+        let challenge = base64url(&Sha256::digest(verifier.as_bytes()));
+        Self { verifier, challenge }
+    }
+}
+
+/// URL-safe string made from `bytes` random bytes
+pub fn random_string(bytes: usize) -> String {
+    let mut buffer = vec![0; bytes];
+    openssl::rand::rand_bytes(&mut buffer).expect("system random source failed");
+    base64url(&buffer)
+}
+
+fn base64url(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// What the authorization server sent back to the redirect URI
+#[derive(Debug)]
+pub struct Callback {
+    pub code: String,
+    /// `iss` parameter (RFC 9207), which AT Protocol requires
+    pub issuer: Option<String>,
+}
+
+/// Listener on 127.0.0.1 that receives the browser's redirect
+pub struct LoopbackListener {
+    listener: TcpListener,
+    port: u16,
+}
+
+impl LoopbackListener {
+    /// Listen on a free port
+    pub async fn bind() -> Result<Self> {
+        Self::bind_port(0).await
+    }
+
+    /// Listen on a fixed port, for servers that only redirect to a
+    /// pre-registered URI
+    pub async fn bind_port(port: u16) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .map_err(|e| anyhow!("Could not listen on 127.0.0.1:{}: {}", port, e))?;
+        let port = listener.local_addr()?.port();
+        Ok(Self { listener, port })
+    }
+
+    pub fn redirect_uri(&self) -> String {
+        format!("http://127.0.0.1:{}/callback", self.port)
+    }
+
+    /// Wait for the browser to return with the authorization code for `state`
+    pub async fn wait(self, state: &str) -> Result<Callback> {
+        tokio::time::timeout(SIGN_IN_TIMEOUT, self.accept(state))
+            .await
+            .map_err(|_| anyhow!("Timed out waiting for sign-in"))?
+    }
+
+    /// Answer requests until one carries `state` or an explicit error.
+    /// Anything else, like a stray or forged request, is turned away.
+    async fn accept(&self, state: &str) -> Result<Callback> {
+        loop {
+            let mut stream = match self.listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("[OAuth] Failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let mut buffer = vec![0; 8192];
+            let read = match stream.read(&mut buffer).await {
+                Ok(read) => read,
+                Err(e) => {
+                    eprintln!("[OAuth] Failed to read the request: {}", e);
+                    continue;
+                }
+            };
+            let request = String::from_utf8_lossy(&buffer[..read]);
+
+            // "GET /callback?code=...&state=... HTTP/1.1"
+            let Some(target) = request.lines().next().and_then(|line| line.split_whitespace().nth(1)) else {
+                respond(&mut stream, "400 Bad Request", "").await;
+                continue;
+            };
+            if !target.starts_with("/callback") {
+                // Browsers also ask for a favicon
+                respond(&mut stream, "404 Not Found", "").await;
+                continue;
+            }
+
+            let Ok(url) = reqwest::Url::parse(&format!("http://127.0.0.1{}", target)) else {
+                respond(&mut stream, "400 Bad Request", "").await;
+                continue;
+            };
+            let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+            let result = if let Some(error) = params.get("error") {
+                let description = params.get("error_description").unwrap_or(error);
+                Err(anyhow!("Sign-in was refused: {}", description))
+            } else if params.get("state").map(String::as_str) != Some(state) {
+                eprintln!("[OAuth] Ignoring a callback that doesn't match the request");
+                respond(&mut stream, "400 Bad Request", "Sign-in response doesn't match the request.").await;
+                continue;
+            } else if let Some(code) = params.get("code") {
+                Ok(Callback {
+                    code: code.clone(),
+                    issuer: params.get("iss").cloned(),
+                })
+            } else {
+                Err(anyhow!("Sign-in response has no authorization code"))
+            };
+
+            let page = match &result {
+                Ok(_) => "Signed in. You can close this window and go back to doh.".to_string(),
+                Err(e) => format!("{}. Go back to doh and try again.", e),
+            };
+            respond(&mut stream, "200 OK", &page).await;
+            return result;
+        }
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        eprintln!("[OAuth] Failed to answer the browser: {}", e);
+    }
+}
+
+/// Show the authorization page in the user's browser
+pub fn open_browser(url: &str) -> Result<()> {
+    std::process::Command::new("xdg-open")
+        .arg(url)
+        .spawn()
+        .map_err(|e| anyhow!("Failed to open the browser: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(port: u16, target: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n", target);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn mismatched_state_keeps_waiting() {
+        let listener = LoopbackListener::bind().await.unwrap();
+        let port = listener.port;
+        let waiting = tokio::spawn(async move { listener.wait("expected").await });
+
+        let forged = get(port, "/callback?code=evil&state=other").await;
+        assert!(forged.starts_with("HTTP/1.1 400"));
+        // A connection that sends nothing doesn't end the sign-in either
+        drop(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+
+        let real = get(port, "/callback?code=good&state=expected").await;
+        assert!(real.starts_with("HTTP/1.1 200"));
+        let callback = waiting.await.unwrap().unwrap();
+        assert_eq!(callback.code, "good");
+    }
+
+    #[tokio::test]
+    async fn explicit_error_ends_the_wait() {
+        let listener = LoopbackListener::bind().await.unwrap();
+        let port = listener.port;
+        let waiting = tokio::spawn(async move { listener.wait("expected").await });
+
+        get(port, "/callback?error=access_denied").await;
+        assert!(waiting.await.unwrap().is_err());
+    }
+}
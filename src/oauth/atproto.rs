@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: MIT
+
+//! AT Protocol OAuth: pushed authorization requests, PKCE and DPoP-bound
+//! tokens
+//!
+//! doh signs in as a loopback client, which the spec lets native apps use
+//! without publishing client metadata.
+
+use super::dpop::{self, DpopKey, DpopNonce};
+use super::{LoopbackListener, OAuthTokens, Pkce, open_browser, random_string, token_response};
+use crate::platforms::bluesky::BlueSkyPlatform;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// The same access an app password grants
+const SCOPE: &str = "atproto transition:generic";
+
+/// A signed-in BlueSky account and what it takes to refresh its tokens
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AtprotoSession {
+    pub did: String,
+    /// The account's PDS, which the tokens are for
+    pub service_url: String,
+    /// Authorization server that issued the tokens
+    pub issuer: String,
+    pub token_endpoint: String,
+    pub client_id: String,
+    /// PEM private key the tokens are bound to
+    pub dpop_key: String,
+    pub tokens: OAuthTokens,
+}
+
+#[derive(Deserialize)]
+struct ProtectedResourceMetadata {
+    authorization_servers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct AuthorizationServerMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    pushed_authorization_request_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct ParResponse {
+    request_uri: String,
+}
+
+/// Sign in to the account behind `handle`. `service_url` skips looking up
+/// the account's PDS, as in the settings.
+pub async fn sign_in(handle: &str, service_url: Option<&str>) -> Result<AtprotoSession> {
+    let handle = handle.trim().trim_start_matches('@');
+    if handle.is_empty() {
+        return Err(anyhow!("Enter your handle first"));
+    }
+
+    let mut platform = BlueSkyPlatform::new(handle.to_string(), String::new());
+    if let Some(service_url) = service_url.filter(|url| !url.trim().is_empty()) {
+        platform = platform.with_service_url(service_url.to_string());
+    }
+    let (did, pds) = platform.discover().await?;
+
+    let client = reqwest::Client::new();
+    let resource: ProtectedResourceMetadata =
+        get_json(&client, &format!("{}/.well-known/oauth-protected-resource", pds)).await?;
+    let issuer = resource
+        .authorization_servers
+        .first()
+        .ok_or_else(|| anyhow!("{} names no authorization server", pds))?
+        .trim_end_matches('/')
+        .to_string();
+    let metadata: AuthorizationServerMetadata =
+        get_json(&client, &format!("{}/.well-known/oauth-authorization-server", issuer)).await?;
+    if metadata.issuer.trim_end_matches('/') != issuer {
+        return Err(anyhow!("Authorization server metadata is for {}, not {}", metadata.issuer, issuer));
+    }
+
+    let listener = LoopbackListener::bind().await?;
+    let redirect_uri = listener.redirect_uri();
+    let client_id = loopback_client_id(&redirect_uri)?;
+    let key = DpopKey::generate()?;
+    let nonce = DpopNonce::default();
+    let pkce = Pkce::new();
+    let state = random_string(16);
+
+    let response = dpop::post_form(
+        &client,
+        &key,
+        &nonce,
+        &metadata.pushed_authorization_request_endpoint,
+        &[
+            ("client_id", client_id.as_str()),
+            ("response_type", "code"),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("scope", SCOPE),
+            ("state", state.as_str()),
+            ("code_challenge", pkce.challenge.as_str()),
+            ("code_challenge_method", "S256"),
+            ("login_hint", handle),
+        ],
+    )
+    .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Authorization request failed: {} - {}", status, error_text));
+    }
+    let par: ParResponse = response.json().await?;
+
+    let authorize_url = reqwest::Url::parse_with_params(
+        &metadata.authorization_endpoint,
+        &[("client_id", client_id.as_str()), ("request_uri", par.request_uri.as_str())],
+    )?;
+    open_browser(authorize_url.as_str())?;
+    let callback = listener.wait(&state).await?;
+    if callback.issuer.as_deref() != Some(metadata.issuer.as_str()) {
+        return Err(anyhow!("Sign-in response came from an unexpected server"));
+    }
+
+    let response = dpop::post_form(
+        &client,
+        &key,
+        &nonce,
+        &metadata.token_endpoint,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", callback.code.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("code_verifier", pkce.verifier.as_str()),
+            ("client_id", client_id.as_str()),
+        ],
+    )
+    .await?;
+    let tokens = token_response(response).await?;
+    // The server must vouch for the account we looked up
+    if tokens.sub.as_deref() != Some(did.as_str()) {
+        return Err(anyhow!("Signed in to a different account than {}", handle));
+    }
+
+    Ok(AtprotoSession {
+        did,
+        service_url: pds,
+        issuer: metadata.issuer,
+        token_endpoint: metadata.token_endpoint,
+        client_id,
+        dpop_key: key.to_pem()?,
+        tokens: tokens.into_tokens(None),
+    })
+}
+
+/// Trade the refresh token for new tokens. Refresh tokens are single-use,
+/// so the result must replace the stored tokens.
+pub async fn refresh(session: &AtprotoSession) -> Result<OAuthTokens> {
+    let refresh_token = session
+        .tokens
+        .refresh_token
+        .as_deref()
+        .ok_or_else(|| anyhow!("No refresh token, sign in again"))?;
+    let key = DpopKey::from_pem(&session.dpop_key)?;
+
+    let response = dpop::post_form(
+        &reqwest::Client::new(),
+        &key,
+        &DpopNonce::default(),
+        &session.token_endpoint,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", session.client_id.as_str()),
+        ],
+    )
+    .await?;
+    Ok(token_response(response).await?.into_tokens(Some(refresh_token.to_string())))
+}
+
+/// Client id of a loopback client: `http://localhost` with no path, and the
+/// redirect URI and scope as query parameters
+fn loopback_client_id(redirect_uri: &str) -> Result<String> {
+    let mut url = reqwest::Url::parse("http://localhost")?;
+    url.query_pairs_mut()
+        .append_pair("redirect_uri", redirect_uri)
+        .append_pair("scope", SCOPE);
+    Ok(format!("http://localhost?{}", url.query().unwrap_or_default()))
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(client: &reqwest::Client, url: &str) -> Result<T> {
+    let response = client.get(url).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow!("{} returned {}", url, status));
+    }
+    Ok(response.json().await?)
+}
@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: MIT
+
+//! DPoP proofs (RFC 9449), which bind AT Protocol tokens to a key only we hold
+
+use super::{base64url, random_string};
+use anyhow::Result;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::nid::Nid;
+use openssl::pkey::Private;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+
+/// P-256 key that signs proofs
+#[derive(Clone)]
+pub struct DpopKey {
+    key: EcKey<Private>,
+}
+
+impl DpopKey {
+    pub fn generate() -> Result<Self> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        Ok(Self {
+            key: EcKey::generate(&group)?,
+        })
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Ok(Self {
+            key: EcKey::private_key_from_pem(pem.as_bytes())?,
+        })
+    }
+
+    pub fn to_pem(&self) -> Result<String> {
+        Ok(String::from_utf8(self.key.private_key_to_pem()?)?)
+    }
+
+    /// Public half as a JWK
+    fn jwk(&self) -> Result<serde_json::Value> {
+        let mut context = BigNumContext::new()?;
+        let mut x = BigNum::new()?;
+        let mut y = BigNum::new()?;
+        self.key
+            .public_key()
+            .affine_coordinates(self.key.group(), &mut x, &mut y, &mut context)?;
+        Ok(serde_json::json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": base64url(&x.to_vec_padded(32)?),
+            "y": base64url(&y.to_vec_padded(32)?),
+        }))
+    }
+
+    /// Proof for one request. `access_token` is given when calling the
+    /// resource server (the PDS) rather than the authorization server.
+    pub fn proof(&self, method: &str, url: &str, nonce: Option<&str>, access_token: Option<&str>) -> Result<String> {
+        // The proof covers the URL without query or fragment
+        let mut target = reqwest::Url::parse(url)?;
+        target.set_query(None);
+        target.set_fragment(None);
+
+        let header = serde_json::json!({
+            "typ": "dpop+jwt",
+            "alg": "ES256",
+            "jwk": self.jwk()?,
+        });
+        let mut claims = serde_json::json!({
+            "jti": random_string(16),
+            "htm": method,
+            "htu": target.as_str(),
+            "iat": chrono::Utc::now().timestamp(),
+        });
+        if let Some(nonce) = nonce {
+            claims["nonce"] = nonce.into();
+        }
+        if let Some(access_token) = access_token {
+            claims["ath"] = base64url(&Sha256::digest(access_token.as_bytes())).into();
+        }
+
+        let signing_input = format!(
+            "{}.{}",
+            base64url(header.to_string().as_bytes()),
+            base64url(claims.to_string().as_bytes())
+        );
+        // JWS wants the bare r and s values, not DER
+        let signature = EcdsaSig::sign(&Sha256::digest(signing_input.as_bytes()), &self.key)?;
+        let mut raw = signature.r().to_vec_padded(32)?;
+        raw.extend(signature.s().to_vec_padded(32)?);
+        Ok(format!("{}.{}", signing_input, base64url(&raw)))
+    }
+}
+
+/// The latest nonce a server handed out, which proofs must echo.
+/// Clones share the value.
+#[derive(Debug, Clone, Default)]
+pub struct DpopNonce(Arc<Mutex<Option<String>>>);
+
+impl DpopNonce {
+    pub fn get(&self) -> Option<String> {
+        self.0.lock().ok().and_then(|nonce| nonce.clone())
+    }
+
+    /// Remember the nonce a response carries. Returns whether it changed,
+    /// i.e. whether a request refused for its nonce is worth repeating.
+    pub fn update(&self, response: &reqwest::Response) -> bool {
+        let Some(fresh) = response
+            .headers()
+            .get("DPoP-Nonce")
+            .and_then(|value| value.to_str().ok())
+        else {
+            return false;
+        };
+        let Ok(mut nonce) = self.0.lock() else {
+            return false;
+        };
+        if nonce.as_deref() == Some(fresh) {
+            return false;
+        }
+        *nonce = Some(fresh.to_string());
+        true
+    }
+
+    /// Whether a response refused our proof's nonce and carried a new one
+    pub fn should_retry(&self, response: &reqwest::Response) -> bool {
+        let refused = matches!(response.status().as_u16(), 400 | 401);
+        self.update(response) && refused
+    }
+}
+
+/// POST a form to an authorization server with a DPoP proof, repeating it
+/// once if the server asks for a fresh nonce
+pub async fn post_form(
+    client: &reqwest::Client,
+    key: &DpopKey,
+    nonce: &DpopNonce,
+    url: &str,
+    form: &[(&str, &str)],
+) -> Result<reqwest::Response> {
+    let mut retried = false;
+    loop {
+        let proof = key.proof("POST", url, nonce.get().as_deref(), None)?;
+        let response = client.post(url).header("DPoP", proof).form(form).send().await?;
+        if !retried && nonce.should_retry(&response) {
+            retried = true;
+            continue;
+        }
+        return Ok(response);
+    }
+}
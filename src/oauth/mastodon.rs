@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MIT
+
+//! Mastodon sign-in: register doh as an app on the instance, then run the
+//! authorization-code flow
+
+use super::{LoopbackListener, Pkce, open_browser, random_string, token_response};
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+
+const SCOPES: &str = "read write";
+
+/// Client registration and the user's access token. Mastodon tokens don't
+/// expire, so there is nothing to refresh.
+#[derive(Debug, Clone)]
+pub struct MastodonSignIn {
+    pub client_id: String,
+    pub client_secret: String,
+    pub access_token: String,
+}
+
+#[derive(Deserialize)]
+struct AppRegistration {
+    client_id: String,
+    client_secret: String,
+}
+
+pub async fn sign_in(instance_url: &str) -> Result<MastodonSignIn> {
+    let instance_url = instance_url.trim().trim_end_matches('/');
+    if instance_url.is_empty() {
+        return Err(anyhow!("Enter your instance URL first"));
+    }
+
+    let listener = LoopbackListener::bind().await?;
+    let redirect_uri = listener.redirect_uri();
+    let client = reqwest::Client::new();
+
+    // The redirect URI includes the listener's port, so register per sign-in
+    let response = client
+        .post(format!("{}/api/v1/apps", instance_url))
+        .form(&[
+            ("client_name", "doh"),
+            ("redirect_uris", redirect_uri.as_str()),
+            ("scopes", SCOPES),
+            ("website", env!("CARGO_PKG_REPOSITORY")),
+        ])
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("App registration failed: {} - {}", status, error_text));
+    }
+    let app: AppRegistration = response.json().await?;
+
+    let pkce = Pkce::new();
+    let state = random_string(16);
+    let authorize_url = reqwest::Url::parse_with_params(
+        &format!("{}/oauth/authorize", instance_url),
+        &[
+            ("response_type", "code"),
+            ("client_id", app.client_id.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("scope", SCOPES),
+            ("state", state.as_str()),
+            ("code_challenge", pkce.challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ],
+    )?;
+    open_browser(authorize_url.as_str())?;
+    let callback = listener.wait(&state).await?;
+
+    let response = client
+        .post(format!("{}/oauth/token", instance_url))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", callback.code.as_str()),
+            ("client_id", app.client_id.as_str()),
+            ("client_secret", app.client_secret.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("code_verifier", pkce.verifier.as_str()),
+            ("scope", SCOPES),
+        ])
+        .send()
+        .await?;
+    let tokens = token_response(response).await?;
+
+    Ok(MastodonSignIn {
+        client_id: app.client_id,
+        client_secret: app.client_secret,
+        access_token: tokens.access_token,
+    })
+}
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: MIT
+
+//! X sign-in with OAuth 2.0 and PKCE
+//!
+//! X has no dynamic client registration, so the user registers a "Native
+//! App" in the developer portal and gives doh its client id.
+
+use super::{LoopbackListener, OAuthTokens, Pkce, open_browser, random_string, token_response};
+use anyhow::{Result, anyhow};
+
+const AUTHORIZE_URL: &str = "https://x.com/i/oauth2/authorize";
+const TOKEN_URL: &str = "https://api.x.com/2/oauth2/token";
+
+/// X only redirects to the callback registered for the app, so the listener
+/// needs a fixed port
+pub const REDIRECT_PORT: u16 = 8765;
+
+/// Posting, deleting and uploading media, plus a refresh token
+const SCOPES: &str = "tweet.read tweet.write users.read media.write offline.access";
+
+pub async fn sign_in(client_id: &str) -> Result<OAuthTokens> {
+    let client_id = client_id.trim();
+    if client_id.is_empty() {
+        return Err(anyhow!("Enter your app's OAuth 2.0 client ID first"));
+    }
+
+    let listener = LoopbackListener::bind_port(REDIRECT_PORT).await?;
+    let redirect_uri = listener.redirect_uri();
+    let pkce = Pkce::new();
+    let state = random_string(16);
+
+    let authorize_url = reqwest::Url::parse_with_params(
+        AUTHORIZE_URL,
+        &[
+            ("response_type", "code"),
+            ("client_id", client_id),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("scope", SCOPES),
+            ("state", state.as_str()),
+            ("code_challenge", pkce.challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ],
+    )?;
+    open_browser(authorize_url.as_str())?;
+    let callback = listener.wait(&state).await?;
+
+    let response = reqwest::Client::new()
+        .post(TOKEN_URL)
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", callback.code.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("code_verifier", pkce.verifier.as_str()),
+            ("client_id", client_id),
+        ])
+        .send()
+        .await?;
+    Ok(token_response(response).await?.into_tokens(None))
+}
+
+/// Trade the refresh token for new tokens. X rotates refresh tokens, so the
+/// result must replace the stored tokens.
+pub async fn refresh(client_id: &str, tokens: &OAuthTokens) -> Result<OAuthTokens> {
+    let refresh_token = tokens
+        .refresh_token
+        .as_deref()
+        .ok_or_else(|| anyhow!("No refresh token, sign in again"))?;
+
+    let response = reqwest::Client::new()
+        .post(TOKEN_URL)
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", client_id),
+        ])
+        .send()
+        .await?;
+    Ok(token_response(response).await?.into_tokens(Some(refresh_token.to_string())))
+}
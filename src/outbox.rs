@@ -0,0 +1,332 @@
+// SPDX-License-Identifier: MIT
+
+//! Persistent outbox for deliveries that failed and should be retried later
+//!
+//! Each entry is one platform delivery, stored as `<id>.json` with its images
+//! next to it as `<id>-<n>.img` in the outbox directory.
+
+use crate::platforms::{PlatformId, Post, PostError, PostImage, PostReference, ThreadOptions};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Give up retrying automatically after this many attempts
+const MAX_ATTEMPTS: u32 = 8;
+
+/// Longest wait between automatic retries
+const MAX_BACKOFF_MINUTES: i64 = 60;
+
+/// Held while an entry's state is checked and changed, so the background
+/// retry and the user can't both act on the same entry
+static STATE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutboxState {
+    /// Will be retried automatically
+    Pending,
+    /// Needs the user to retry or discard it
+    Failed,
+    /// Being sent by the background retry right now
+    Sending,
+}
+
+/// A delivery waiting in the outbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: String,
+    pub platform: PlatformId,
+    pub text: String,
+    pub image_count: usize,
+    /// Alt text of each image
+    #[serde(default)]
+    pub image_alts: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: String,
+    pub state: OutboxState,
+    /// History entry of the original post, so a late delivery can be added to it
+    #[serde(default)]
+    pub history_id: Option<String>,
+    /// Post the text as a thread
+    #[serde(default)]
+    pub thread: Option<ThreadOptions>,
+    /// Post replied to or quoted
+    #[serde(default)]
+    pub reference: Option<PostReference>,
+    /// The last attempt failed in a way that may have published the post
+    /// anyway, so it isn't retried without the user checking first
+    #[serde(default)]
+    pub maybe_posted: bool,
+    /// Delivery id of the original attempt, sent again with every retry
+    #[serde(default)]
+    pub delivery_id: Option<String>,
+}
+
+impl OutboxEntry {
+    /// Whether the background retry should pick this entry up now
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.state == OutboxState::Pending && self.next_attempt_at <= now
+    }
+}
+
+/// On-disk outbox
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    dir: PathBuf,
+}
+
+impl Outbox {
+    /// Open the outbox under the XDG data directory
+    pub fn open_default() -> Result<Self> {
+        let dir = crate::config::data_dir().ok_or_else(|| anyhow!("No data directory available"))?;
+        Self::open(dir.join("outbox"))
+    }
+
+    pub fn open(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// All entries, oldest first. Unreadable entries are skipped.
+    pub fn list(&self) -> Vec<OutboxEntry> {
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        let mut entries: Vec<OutboxEntry> = dir
+            .flatten()
+            .map(|file| file.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let json = std::fs::read_to_string(&path).ok()?;
+                match serde_json::from_str(&json) {
+                    Ok(entry) => Some(entry),
+                    Err(e) => {
+                        eprintln!("[Outbox] Skipping unreadable entry {}: {}", path.display(), e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.created_at);
+        entries
+    }
+
+    /// Store a failed delivery. Transient errors that can't have published
+    /// anything are retried automatically, anything else waits for the user.
+    pub fn enqueue(
+        &self,
+        platform: PlatformId,
+        post: &Post,
+        error: &PostError,
+        history_id: Option<String>,
+    ) -> Result<OutboxEntry> {
+        let now = Utc::now();
+        let id = format!(
+            "{}-{}",
+            now.format("%Y%m%dT%H%M%S%.6f"),
+            platform.name().to_lowercase()
+        );
+
+        for (i, image) in post.images.iter().enumerate() {
+            std::fs::write(self.image_path(&id, i), &image.bytes)?;
+        }
+
+        let mut entry = OutboxEntry {
+            id,
+            platform,
+            text: post.text.clone(),
+            image_count: post.images.len(),
+            image_alts: post.images.iter().map(|image| image.alt.clone()).collect(),
+            created_at: now,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: String::new(),
+            state: OutboxState::Pending,
+            history_id,
+            thread: post.thread,
+            reference: post.reference.clone(),
+            maybe_posted: false,
+            delivery_id: post.delivery_id.clone(),
+        };
+        self.record_failure(&mut entry, error)?;
+
+        eprintln!("[Outbox] Queued {} delivery {}", platform, entry.id);
+        Ok(entry)
+    }
+
+    /// Claim an entry for sending, so it can't be removed or sent by anyone
+    /// else meanwhile. Returns `false` if it is no longer due.
+    pub fn start_sending(&self, entry: &mut OutboxEntry) -> Result<bool> {
+        let _lock = STATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let Ok(current) = self.load(&entry.id) else {
+            return Ok(false);
+        };
+        if !current.is_due(Utc::now()) {
+            return Ok(false);
+        }
+        *entry = current;
+        entry.state = OutboxState::Sending;
+        self.save(entry)?;
+        Ok(true)
+    }
+
+    /// Mark entries a previous run was still sending when it stopped. They
+    /// may have gone out, so they wait for the user.
+    pub fn recover_interrupted(&self) {
+        for mut entry in self.list().into_iter().filter(|entry| entry.state == OutboxState::Sending) {
+            entry.state = OutboxState::Failed;
+            entry.maybe_posted = true;
+            entry.last_error = "Interrupted while sending".to_string();
+            if let Err(e) = self.save(&entry) {
+                eprintln!("[Outbox] Failed to update {}: {}", entry.id, e);
+            }
+        }
+    }
+
+    /// Load the images stored with an entry
+    pub fn load_images(&self, entry: &OutboxEntry) -> Result<Vec<PostImage>> {
+        (0..entry.image_count)
+            .map(|i| {
+                let bytes = std::fs::read(self.image_path(&entry.id, i))?;
+                let alt = entry.image_alts.get(i).cloned().unwrap_or_default();
+                Ok(PostImage::new(bytes, alt))
+            })
+            .collect()
+    }
+
+    /// Record another failed attempt and schedule the next one
+    pub fn record_failure(&self, entry: &mut OutboxEntry, error: &PostError) -> Result<()> {
+        entry.attempts += 1;
+        entry.last_error = format!("{} ({})", error.message, error.kind);
+        entry.maybe_posted = error.kind.is_transient() && !error.retry_safe;
+
+        if error.kind.is_transient() && error.retry_safe && entry.attempts < MAX_ATTEMPTS {
+            let backoff = 2i64.saturating_pow(entry.attempts - 1).min(MAX_BACKOFF_MINUTES);
+            entry.state = OutboxState::Pending;
+            entry.next_attempt_at = Utc::now() + Duration::minutes(backoff);
+        } else {
+            entry.state = OutboxState::Failed;
+        }
+
+        self.save(entry)
+    }
+
+    /// Record an attempt that published part of a thread before failing.
+    /// Sending it again would repeat those parts, so it waits for the user.
+    pub fn record_partial(&self, entry: &mut OutboxEntry, error: &PostError) -> Result<()> {
+        entry.attempts += 1;
+        entry.last_error = format!("{} ({})", error.message, error.kind);
+        entry.maybe_posted = true;
+        entry.state = OutboxState::Failed;
+        self.save(entry)
+    }
+
+    /// Queue an entry for an immediate retry
+    pub fn retry_now(&self, id: &str) -> Result<()> {
+        let _lock = STATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entry = self.load(id)?;
+        if entry.state == OutboxState::Sending {
+            return Err(anyhow!("{} is being sent", id));
+        }
+        entry.state = OutboxState::Pending;
+        entry.next_attempt_at = Utc::now();
+        self.save(&entry)
+    }
+
+    /// Delete an entry and its images, unless it is being sent
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let _lock = STATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = self.load(id)?;
+        if entry.state == OutboxState::Sending {
+            return Err(anyhow!("{} is being sent", id));
+        }
+        self.delete(&entry)
+    }
+
+    /// Delete an entry the background retry delivered
+    pub fn delivered(&self, entry: &OutboxEntry) -> Result<()> {
+        let _lock = STATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        self.delete(entry)
+    }
+
+    fn delete(&self, entry: &OutboxEntry) -> Result<()> {
+        for i in 0..entry.image_count {
+            let _ = std::fs::remove_file(self.image_path(&entry.id, i));
+        }
+        std::fs::remove_file(self.entry_path(&entry.id))?;
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<OutboxEntry> {
+        let json = std::fs::read_to_string(self.entry_path(id))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    fn save(&self, entry: &OutboxEntry) -> Result<()> {
+        // Write to a temporary file first so a crash can't leave half an entry
+        let path = self.entry_path(&entry.id);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(entry)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    fn entry_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    fn image_path(&self, id: &str, index: usize) -> PathBuf {
+        self.dir.join(format!("{}-{}.img", id, index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platforms::ErrorKind;
+
+    fn temp_outbox(name: &str) -> Outbox {
+        let dir = std::env::temp_dir().join(format!("doh-outbox-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Outbox::open(dir).unwrap()
+    }
+
+    #[test]
+    fn entry_being_sent_is_left_alone() {
+        let outbox = temp_outbox("sending");
+        let error = PostError::new(ErrorKind::Network, "offline");
+        let entry = outbox
+            .enqueue(PlatformId::Mastodon, &Post::new("hello".to_string(), Vec::new()), &error, None)
+            .unwrap();
+        outbox.retry_now(&entry.id).unwrap();
+
+        let mut claimed = entry.clone();
+        assert!(outbox.start_sending(&mut claimed).unwrap());
+        assert!(!outbox.start_sending(&mut entry.clone()).unwrap());
+        assert!(outbox.remove(&entry.id).is_err());
+        assert!(outbox.retry_now(&entry.id).is_err());
+
+        outbox.delivered(&claimed).unwrap();
+        assert!(outbox.list().is_empty());
+    }
+
+    #[test]
+    fn interrupted_send_waits_for_the_user() {
+        let outbox = temp_outbox("interrupted");
+        let error = PostError::new(ErrorKind::Network, "offline");
+        let mut entry = outbox
+            .enqueue(PlatformId::Mastodon, &Post::new("hello".to_string(), Vec::new()), &error, None)
+            .unwrap();
+        outbox.retry_now(&entry.id).unwrap();
+        assert!(outbox.start_sending(&mut entry).unwrap());
+
+        outbox.recover_interrupted();
+        let entry = outbox.load(&entry.id).unwrap();
+        assert_eq!(entry.state, OutboxState::Failed);
+        assert!(entry.maybe_posted);
+    }
+}
@@ -2,14 +2,53 @@
 
 //! BlueSky platform adapter using AT Protocol
 
-use super::{Platform, Post, PostResult};
-use anyhow::{Result, anyhow};
+mod facets;
+
+use facets::{Facet, Feature, Segment};
+use super::reference::{self, ParsedUrl};
+use super::retry::{Idempotency, RetryPolicy};
+use super::{ErrorKind, Platform, PlatformId, Post, PostError, PostOutcome, RemoteId, ReplyRef};
+use crate::credentials::{BlueSkySession, Credentials};
+use crate::link_preview::{self, LinkPreviewFetcher};
+use crate::oauth::atproto::{self, AtprotoSession};
+use crate::oauth::{DpopKey, DpopNonce};
+use futures_util::future::BoxFuture;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const DEFAULT_SERVICE_URL: &str = "https://bsky.social";
+
+/// Directory that hosts `did:plc` documents
+const PLC_DIRECTORY_URL: &str = "https://plc.directory";
+
+/// Refresh access tokens this many seconds before they expire
+const TOKEN_EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// Public AppView, used by default to look up other people's posts without a session
+const DEFAULT_APPVIEW_URL: &str = "https://public.api.bsky.app";
 
 pub struct BlueSkyPlatform {
+    /// XRPC host; replaced by the account's PDS at login unless overridden
+    service_url: String,
+    discover_service: bool,
+    /// AppView that answers lookups of posts to reply to or quote
+    appview_url: String,
     handle: String,
     app_password: String,
-    access_token: Option<String>,
+    /// Tokens and DID, known after login; records are written to the DID's repo.
+    /// With OAuth this holds the DPoP-bound access token and no refresh JWT.
+    session: Option<BlueSkySession>,
+    oauth: Option<OAuthSession>,
+    retry: RetryPolicy,
+}
+
+/// Browser sign-in. Clones share the session, so a refresh is seen by
+/// every later request.
+#[derive(Clone)]
+struct OAuthSession {
+    session: Arc<tokio::sync::Mutex<AtprotoSession>>,
+    key: DpopKey,
+    nonce: DpopNonce,
 }
 
 #[derive(Serialize)]
@@ -18,6 +57,7 @@ struct LoginRequest {
     password: String,
 }
 
+/// Answer to both createSession and refreshSession
 #[derive(Deserialize)]
 #[allow(dead_code)]
 struct LoginResponse {
@@ -43,8 +83,80 @@ struct PostRecord {
     created_at: String,
     #[serde(rename = "$type")]
     record_type: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    facets: Vec<Facet>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    embed: Option<ImagesEmbed>,
+    embed: Option<Embed>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply: Option<ReplyRecord>,
+}
+
+#[derive(Serialize)]
+struct ReplyRecord {
+    root: StrongRef,
+    parent: StrongRef,
+}
+
+/// `com.atproto.repo.strongRef`
+#[derive(Serialize)]
+struct StrongRef {
+    uri: String,
+    cid: String,
+}
+
+impl StrongRef {
+    fn from_remote_id(remote_id: &RemoteId) -> Result<Self, PostError> {
+        match remote_id {
+            RemoteId::Record { uri, cid } => Ok(Self {
+                uri: uri.clone(),
+                cid: cid.clone(),
+            }),
+            _ => Err(PostError::new(ErrorKind::Validation, "Can only reply to a BlueSky record")),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Embed {
+    Images(ImagesEmbed),
+    Record(RecordEmbed),
+    RecordWithMedia(RecordWithMediaEmbed),
+    External(ExternalEmbed),
+}
+
+/// `app.bsky.embed.record`, a quoted post
+#[derive(Serialize)]
+struct RecordEmbed {
+    #[serde(rename = "$type")]
+    embed_type: String,
+    record: StrongRef,
+}
+
+/// `app.bsky.embed.recordWithMedia`, a quoted post plus images
+#[derive(Serialize)]
+struct RecordWithMediaEmbed {
+    #[serde(rename = "$type")]
+    embed_type: String,
+    record: RecordEmbed,
+    media: ImagesEmbed,
+}
+
+/// `app.bsky.embed.external`, a link card
+#[derive(Serialize)]
+struct ExternalEmbed {
+    #[serde(rename = "$type")]
+    embed_type: String,
+    external: External,
+}
+
+#[derive(Serialize)]
+struct External {
+    uri: String,
+    title: String,
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumb: Option<BlobRef>,
 }
 
 #[derive(Serialize)]
@@ -77,6 +189,68 @@ struct BlobReference {
     link: String,
 }
 
+#[derive(Serialize)]
+struct DeleteRecordRequest {
+    repo: String,
+    collection: String,
+    rkey: String,
+}
+
+#[derive(Deserialize)]
+struct ResolveHandleResponse {
+    did: String,
+}
+
+#[derive(Deserialize)]
+struct DidDocument {
+    #[serde(default)]
+    service: Vec<DidService>,
+}
+
+#[derive(Deserialize)]
+struct DidService {
+    id: String,
+    #[serde(rename = "type")]
+    service_type: String,
+    /// A URL for the PDS, though the DID spec also allows maps and lists
+    #[serde(rename = "serviceEndpoint")]
+    service_endpoint: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct GetPostsResponse {
+    posts: Vec<PostView>,
+}
+
+#[derive(Deserialize)]
+struct PostView {
+    uri: String,
+    cid: String,
+    record: PostViewRecord,
+}
+
+#[derive(Deserialize)]
+struct PostViewRecord {
+    reply: Option<PostViewReply>,
+}
+
+#[derive(Deserialize)]
+struct PostViewReply {
+    root: StrongRefView,
+}
+
+#[derive(Deserialize)]
+struct StrongRefView {
+    uri: String,
+    cid: String,
+}
+
+#[derive(Deserialize)]
+struct CreateRecordResponse {
+    uri: String,
+    cid: String,
+}
+
 #[derive(Deserialize)]
 struct UploadBlobResponse {
     blob: BlobRef,
@@ -85,154 +259,729 @@ struct UploadBlobResponse {
 impl BlueSkyPlatform {
     pub fn new(handle: String, app_password: String) -> Self {
         Self {
+            service_url: DEFAULT_SERVICE_URL.to_string(),
+            discover_service: true,
+            appview_url: DEFAULT_APPVIEW_URL.to_string(),
             handle,
             app_password,
-            access_token: None,
+            session: None,
+            oauth: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+    
+    /// Create the adapter if BlueSky credentials are configured
+    pub fn from_credentials(credentials: &Credentials) -> Option<Self> {
+        if !credentials.has_bluesky() {
+            return None;
+        }
+        let platform = if let Some(session) = credentials.bluesky_oauth.clone() {
+            let handle = credentials.bluesky_handle.clone().unwrap_or_else(|| session.did.clone());
+            Self::new(handle, String::new()).with_oauth(session)
+        } else {
+            let platform = Self::new(
+                credentials.bluesky_handle.clone()?.trim().trim_start_matches('@').to_string(),
+                credentials.bluesky_app_password.clone()?,
+            );
+            match credentials.bluesky_service_url.clone().filter(|url| !url.trim().is_empty()) {
+                Some(url) => platform.with_service_url(url),
+                None => platform,
+            }
+        };
+        Some(match credentials.bluesky_appview_url.clone().filter(|url| !url.trim().is_empty()) {
+            Some(url) => platform.with_appview_url(url),
+            None => platform,
+        })
+    }
+    
+    /// Override the XRPC service (e.g. a local PDS or mock server) instead
+    /// of looking up the account's PDS
+    pub fn with_service_url(mut self, service_url: String) -> Self {
+        self.service_url = service_url.trim().trim_end_matches('/').to_string();
+        self.discover_service = false;
+        self
+    }
+    
+    /// Look up posts on another AppView, e.g. one serving a separate network
+    pub fn with_appview_url(mut self, appview_url: String) -> Self {
+        self.appview_url = appview_url.trim().trim_end_matches('/').to_string();
+        self
+    }
+    
+    /// Use a browser sign-in instead of the app password
+    pub fn with_oauth(mut self, session: AtprotoSession) -> Self {
+        match DpopKey::from_pem(&session.dpop_key) {
+            Ok(key) => {
+                self.service_url = session.service_url.clone();
+                self.discover_service = false;
+                self.oauth = Some(OAuthSession {
+                    session: Arc::new(tokio::sync::Mutex::new(session)),
+                    key,
+                    nonce: DpopNonce::default(),
+                });
+            }
+            Err(e) => eprintln!("[BlueSky] Unusable OAuth session key, sign in again: {}", e),
+        }
+        self
+    }
+    
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    
+    /// DID and PDS of the configured account
+    pub async fn discover(&self) -> Result<(String, String), PostError> {
+        let did = self.resolve_identity().await?;
+        let pds = if self.discover_service {
+            self.find_pds(&did).await?
+        } else {
+            self.service_url.clone()
+        };
+        Ok((did, pds))
+    }
+    
+    /// Make sure we hold a usable session: the stored one if it is still
+    /// valid, otherwise a refreshed one, and only as a last resort a new login
+    async fn authenticate(&mut self) -> Result<(), PostError> {
+        if let Some(oauth) = &self.oauth {
+            let session = refresh_oauth(oauth, None).await?;
+            self.use_oauth_session(session);
+            return Ok(());
+        }
+        
+        if self.session.is_none() {
+            self.session = BlueSkySession::load().filter(|session| {
+                session.handle == self.handle
+                    && (self.discover_service || session.service_url == self.service_url)
+            });
+        }
+        
+        let Some(session) = self.session.clone() else {
+            return self.login().await;
+        };
+        self.service_url = session.service_url.clone();
+        if !token_expired(&session.access_jwt) {
+            return Ok(());
+        }
+        
+        match self.refresh(&session).await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                eprintln!("[BlueSky] Session refresh failed, logging in again: {}", e);
+                self.session = None;
+                BlueSkySession::clear();
+                self.login().await
+            }
         }
     }
     
-    async fn login(&mut self) -> Result<()> {
+    /// Replace an access token the PDS turned down. The refresh token is
+    /// kept unless the refresh itself is rejected.
+    async fn refresh_access(&mut self) -> Result<(), PostError> {
+        let rejected = self.access_token()?.to_string();
+        if let Some(oauth) = self.oauth.clone() {
+            let session = refresh_oauth(&oauth, Some(&rejected)).await?;
+            self.use_oauth_session(session);
+            return Ok(());
+        }
+        
+        let Some(mut session) = self.session.take() else {
+            return self.login().await;
+        };
+        // An empty access token counts as expired, so a later post refreshes
+        // too if this attempt doesn't get through
+        session.access_jwt.clear();
+        if let Err(e) = session.save() {
+            eprintln!("[BlueSky] {}", e);
+        }
+        self.session = Some(session.clone());
+        
+        match self.refresh(&session).await {
+            Ok(()) => Ok(()),
+            Err(e) if matches!(e.kind, ErrorKind::Auth | ErrorKind::Validation) => {
+                eprintln!("[BlueSky] Session refresh rejected, logging in again: {}", e);
+                self.forget_session();
+                self.login().await
+            }
+            Err(e) => Err(e),
+        }
+    }
+    
+    /// Trade the refresh token for new tokens
+    async fn refresh(&mut self, session: &BlueSkySession) -> Result<(), PostError> {
+        if token_expired(&session.refresh_jwt) {
+            return Err(PostError::new(ErrorKind::Auth, "Refresh token expired"));
+        }
+        
         let client = reqwest::Client::new();
-        let response = client
-            .post("https://bsky.social/xrpc/com.atproto.server.createSession")
-            .json(&LoginRequest {
-                identifier: self.handle.clone(),
-                password: self.app_password.clone(),
+        let response = self
+            .retry
+            .send("BlueSky refreshSession", Idempotency::Idempotent, || {
+                Ok(client
+                    .post(format!("{}/xrpc/com.atproto.server.refreshSession", self.service_url))
+                    .header("Authorization", format!("Bearer {}", session.refresh_jwt)))
+            })
+            .await?;
+        
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(PostError::from_status(status, &error_text, Idempotency::Idempotent));
+        }
+        
+        let refreshed: LoginResponse = response.json().await?;
+        eprintln!("[BlueSky] Session refreshed");
+        self.store_session(refreshed);
+        Ok(())
+    }
+    
+    /// Keep new tokens for this adapter and for later posts
+    fn store_session(&mut self, response: LoginResponse) {
+        let session = BlueSkySession {
+            handle: self.handle.clone(),
+            did: response.did,
+            service_url: self.service_url.clone(),
+            access_jwt: response.access_jwt,
+            refresh_jwt: response.refresh_jwt,
+        };
+        if let Err(e) = session.save() {
+            eprintln!("[BlueSky] {}", e);
+        }
+        self.session = Some(session);
+    }
+    
+    /// Use the tokens of an OAuth session
+    fn use_oauth_session(&mut self, session: AtprotoSession) {
+        self.service_url = session.service_url.clone();
+        self.session = Some(BlueSkySession {
+            handle: self.handle.clone(),
+            did: session.did,
+            service_url: session.service_url,
+            access_jwt: session.tokens.access_token,
+            refresh_jwt: String::new(),
+        });
+    }
+    
+    /// Drop a session the server no longer accepts
+    fn forget_session(&mut self) {
+        self.session = None;
+        // OAuth tokens aren't kept in the session entry
+        if self.oauth.is_none() {
+            BlueSkySession::clear();
+        }
+    }
+    
+    fn access_token(&self) -> Result<&str, PostError> {
+        self.session
+            .as_ref()
+            .map(|session| session.access_jwt.as_str())
+            .ok_or_else(|| PostError::new(ErrorKind::Auth, "Not authenticated"))
+    }
+    
+    /// Send an authenticated request to the PDS. OAuth sessions sign every
+    /// attempt with a DPoP proof and repeat the request once if the PDS asks
+    /// for a fresh nonce.
+    async fn send_authorized<F>(
+        &self,
+        label: &str,
+        idempotency: Idempotency,
+        method: reqwest::Method,
+        url: &str,
+        build: F,
+    ) -> Result<reqwest::Response, PostError>
+    where
+        F: Fn(reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder, PostError>,
+    {
+        let token = self.access_token()?;
+        let client = reqwest::Client::new();
+        let mut retried = false;
+        loop {
+            let response = self
+                .retry
+                .send(label, idempotency, || {
+                    let request = client.request(method.clone(), url);
+                    let request = match &self.oauth {
+                        Some(oauth) => {
+                            let proof = oauth
+                                .key
+                                .proof(method.as_str(), url, oauth.nonce.get().as_deref(), Some(token))
+                                .map_err(|e| PostError::new(ErrorKind::Auth, format!("Failed to sign DPoP proof: {}", e)))?;
+                            request
+                                .header("Authorization", format!("DPoP {}", token))
+                                .header("DPoP", proof)
+                        }
+                        None => request.header("Authorization", format!("Bearer {}", token)),
+                    };
+                    build(request)
+                })
+                .await?;
+            
+            let nonce_refused = self.oauth.as_ref().is_some_and(|oauth| oauth.nonce.should_retry(&response));
+            if nonce_refused && !retried {
+                retried = true;
+                continue;
+            }
+            return Ok(response);
+        }
+    }
+    
+    /// Send an authenticated request and return the response if it
+    /// succeeded. When the PDS turns the access token down (401, or 400
+    /// `ExpiredToken`) the session is refreshed and the request sent once
+    /// more; it was refused outright, so repeating it can't act twice.
+    async fn send_refreshing<F>(
+        &mut self,
+        label: &str,
+        idempotency: Idempotency,
+        method: reqwest::Method,
+        url: &str,
+        build: F,
+    ) -> Result<reqwest::Response, PostError>
+    where
+        F: Fn(reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder, PostError>,
+    {
+        let mut refreshed = false;
+        loop {
+            let response = self.send_authorized(label, idempotency, method.clone(), url, &build).await?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response);
+            }
+            
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            let token_refused = status == reqwest::StatusCode::UNAUTHORIZED
+                || (status == reqwest::StatusCode::BAD_REQUEST && error_text.contains("ExpiredToken"));
+            if token_refused && !refreshed {
+                eprintln!("[BlueSky] {} refused the access token, refreshing the session", label);
+                refreshed = true;
+                self.refresh_access().await?;
+                continue;
+            }
+            return Err(PostError::from_status(status, &error_text, idempotency));
+        }
+    }
+    
+    async fn login(&mut self) -> Result<(), PostError> {
+        if self.discover_service {
+            let (did, pds) = self.discover().await?;
+            self.service_url = pds;
+            eprintln!("[BlueSky] {} is hosted on {}", did, self.service_url);
+        }
+        
+        let client = reqwest::Client::new();
+        let request = LoginRequest {
+            identifier: self.handle.clone(),
+            password: self.app_password.clone(),
+        };
+        let response = self
+            .retry
+            .send("BlueSky createSession", Idempotency::Idempotent, || {
+                Ok(client
+                    .post(format!("{}/xrpc/com.atproto.server.createSession", self.service_url))
+                    .json(&request))
             })
-            .send()
             .await?;
             
         if !response.status().is_success() {
-            return Err(anyhow!("Login failed: {}", response.status()));
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let kind = match status.as_u16() {
+                // createSession answers bad credentials with 401 or 400
+                400 | 401 => ErrorKind::Auth,
+                _ => ErrorKind::from_status(status),
+            };
+            return Err(PostError::new(kind, format!("Login failed: {} - {}", status, error_text)));
         }
         
         let login_response: LoginResponse = response.json().await?;
-        self.access_token = Some(login_response.access_jwt);
+        eprintln!("[BlueSky] Logged in as {}", login_response.did);
+        self.store_session(login_response);
         Ok(())
     }
+    
+    /// DID of the configured account. Handles are resolved through their
+    /// well-known URL first, then by asking the default service, which also
+    /// covers DNS-verified handles.
+    async fn resolve_identity(&self) -> Result<String, PostError> {
+        if self.handle.starts_with("did:") {
+            return Ok(self.handle.clone());
+        }
+        
+        // Most handles don't serve this, so don't spend retries on it
+        let well_known = reqwest::Client::new()
+            .get(format!("https://{}/.well-known/atproto-did", self.handle))
+            .timeout(std::time::Duration::from_secs(5))
+            .send()
+            .await;
+        let did = match well_known {
+            Ok(response) if response.status().is_success() => response.text().await.unwrap_or_default(),
+            _ => String::new(),
+        };
+        if did.trim().starts_with("did:") {
+            return Ok(did.trim().to_string());
+        }
+        
+        self.resolve_handle(&self.handle).await.map_err(|e| {
+            PostError::new(e.kind, format!("Could not resolve handle {}: {}", self.handle, e.message))
+        })
+    }
+    
+    /// PDS endpoint listed in a DID document
+    async fn find_pds(&self, did: &str) -> Result<String, PostError> {
+        let document_url = if did.starts_with("did:plc:") {
+            format!("{}/{}", PLC_DIRECTORY_URL, did)
+        } else if let Some(host) = did.strip_prefix("did:web:") {
+            format!("https://{}/.well-known/did.json", host.replace("%3A", ":"))
+        } else {
+            return Err(PostError::new(ErrorKind::Auth, format!("Unsupported DID method: {}", did)));
+        };
+        
+        let client = reqwest::Client::new();
+        let response = self
+            .retry
+            .send("BlueSky DID document", Idempotency::Idempotent, || {
+                Ok(client.get(&document_url))
+            })
+            .await?;
+        
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(PostError::from_status(status, &error_text, Idempotency::Idempotent));
+        }
+        
+        let document: DidDocument = response.json().await?;
+        document
+            .service
+            .iter()
+            .find(|service| service.id.ends_with("#atproto_pds") && service.service_type == "AtprotoPersonalDataServer")
+            .and_then(|service| service.service_endpoint.as_str())
+            .map(|url| url.trim_end_matches('/').to_string())
+            .ok_or_else(|| PostError::new(ErrorKind::Auth, format!("No PDS listed for {}", did)))
+    }
 }
 
 impl Platform for BlueSkyPlatform {
-    fn name(&self) -> &'static str {
-        "BlueSky"
+    fn id(&self) -> PlatformId {
+        PlatformId::BlueSky
+    }
+    
+    fn is_authenticated(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            self.oauth.is_some() || (!self.handle.is_empty() && !self.app_password.is_empty())
+        })
     }
     
-    async fn is_authenticated(&self) -> bool {
-        !self.handle.is_empty() && !self.app_password.is_empty()
+    fn post<'a>(&'a self, post: &'a Post) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+        Box::pin(self.publish(post))
     }
     
-    async fn post(&self, post: &Post) -> Result<PostResult> {
+    fn delete<'a>(&'a self, remote_id: &'a RemoteId) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+        Box::pin(self.remove(remote_id))
+    }
+    
+    fn resolve<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<ReplyRef, PostError>> {
+        Box::pin(self.lookup(url))
+    }
+}
+
+impl BlueSkyPlatform {
+    async fn publish(&self, post: &Post) -> Result<PostOutcome, PostError> {
         use crate::image_utils;
         
-        let mut platform = self.clone();
+        let reply = match &post.reply {
+            Some(ReplyRef { root, parent }) => Some(ReplyRecord {
+                root: StrongRef::from_remote_id(root)?,
+                parent: StrongRef::from_remote_id(parent)?,
+            }),
+            None => None,
+        };
         
-        // Login if not already authenticated
-        if platform.access_token.is_none() {
-            platform.login().await?;
-        }
+        let quote = post.quote.as_ref().map(StrongRef::from_remote_id).transpose()?;
         
-        let token = platform.access_token.as_ref()
-            .ok_or_else(|| anyhow!("Not authenticated"))?;
+        let mut platform = self.clone();
+        platform.authenticate().await?;
         
         // Upload images if any (max 4 images, 1MB each)
         let mut image_refs = Vec::new();
-        for (i, image_bytes) in post.images.iter().enumerate().take(4) {
-            eprintln!("[BlueSky] Processing image {} ({} bytes)", i + 1, image_bytes.len());
+        for (i, image) in post.images.iter().enumerate().take(4) {
+            eprintln!("[BlueSky] Processing image {} ({} bytes)", i + 1, image.bytes.len());
             
             // Compress to 1MB max
             let processor = image_utils::ImageProcessor::new()
                 .with_max_size(1_000_000) // 1MB
                 .with_max_dimension(2000); // Max resolution
             
-            let processed_bytes = match processor.process(image_bytes) {
+            let processed_bytes = match processor.process(&image.bytes) {
                 Ok(bytes) => {
                     eprintln!("[BlueSky] Image {} processed to {} bytes", i + 1, bytes.len());
                     bytes
                 }
                 Err(e) => {
                     eprintln!("[BlueSky] Failed to process image {}: {}", i + 1, e);
-                    return Ok(PostResult::Error {
-                        message: format!("Failed to process image: {}", e),
-                    });
+                    return Err(PostError::new(
+                        ErrorKind::Media,
+                        format!("Failed to process image: {}", e),
+                    ));
                 }
             };
             
-            match platform.upload_blob(&processed_bytes, token).await {
+            match platform.upload_blob(&processed_bytes).await {
                 Ok(blob_ref) => {
                     eprintln!("[BlueSky] Image {} uploaded successfully", i + 1);
                     image_refs.push(ImageRef {
-                        alt: String::new(),
+                        alt: image.alt.clone(),
                         image: blob_ref,
                     });
                 }
                 Err(e) => {
                     eprintln!("[BlueSky] Failed to upload image {}: {}", i + 1, e);
-                    return Ok(PostResult::Error {
-                        message: format!("Failed to upload image: {}", e),
-                    });
+                    return Err(e.into_media_error());
                 }
             }
         }
         
+        // Images and quotes take the embed; otherwise the first link gets a card
+        let embed = match embed(image_refs, quote) {
+            Some(embed) => Some(embed),
+            None => platform.link_card(&post.text).await.map(Embed::External),
+        };
+        
         // Create post record
         let now = chrono::Utc::now().to_rfc3339();
         let record = PostRecord {
             text: post.text.clone(),
             created_at: now,
             record_type: "app.bsky.feed.post".to_string(),
-            embed: if image_refs.is_empty() {
-                None
-            } else {
-                Some(ImagesEmbed {
-                    embed_type: "app.bsky.embed.images".to_string(),
-                    images: image_refs,
-                })
-            },
+            facets: platform.facets(&post.text).await,
+            embed,
+            reply,
         };
         
         let request = CreatePostRequest {
-            repo: platform.handle.clone(),
+            repo: platform.session.as_ref().map_or_else(|| platform.handle.clone(), |session| session.did.clone()),
             collection: "app.bsky.feed.post".to_string(),
             record,
         };
         
-        let client = reqwest::Client::new();
-        let response = client
-            .post("https://bsky.social/xrpc/com.atproto.repo.createRecord")
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&request)
-            .send()
+        // Creating the record publishes the post, so only retry when the
+        // server refused it outright
+        let url = format!("{}/xrpc/com.atproto.repo.createRecord", platform.service_url);
+        let response = platform
+            .send_refreshing("BlueSky createRecord", Idempotency::NonIdempotent, reqwest::Method::POST, &url, |builder| {
+                Ok(builder.json(&request))
+            })
             .await?;
             
-        if response.status().is_success() {
-            Ok(PostResult::Success { url: None })
-        } else {
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            Ok(PostResult::Error { 
-                message: format!("Failed to post: {}", error_text) 
-            })
-        }
+        let record: CreateRecordResponse = response.json().await?;
+        Ok(PostOutcome {
+            url: post_url(&record.uri),
+            remote_id: Some(RemoteId::Record {
+                uri: record.uri,
+                cid: record.cid,
+            }),
+            ..PostOutcome::success(PlatformId::BlueSky)
+        })
     }
 }
 
 impl BlueSkyPlatform {
-    async fn upload_blob(&self, image_bytes: &[u8], token: &str) -> Result<BlobRef> {
-        use crate::image_utils;
+    /// Look up the DID behind a handle
+    async fn resolve_handle(&self, handle: &str) -> Result<String, PostError> {
+        let client = reqwest::Client::new();
+        let response = self
+            .retry
+            .send("BlueSky resolveHandle", Idempotency::Idempotent, || {
+                Ok(client
+                    .get(format!("{}/xrpc/com.atproto.identity.resolveHandle", self.service_url))
+                    .query(&[("handle", handle)]))
+            })
+            .await?;
         
-        let mime_type = image_utils::get_mime_type(image_bytes)?;
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(PostError::from_status(status, &error_text, Idempotency::Idempotent));
+        }
+        Ok(response.json::<ResolveHandleResponse>().await?.did)
+    }
+    
+    /// Facets for the links, mentions and hashtags in a post. Mentions of
+    /// handles that don't resolve are left as plain text.
+    async fn facets(&self, text: &str) -> Vec<Facet> {
+        let mut facets = Vec::new();
+        for (range, segment) in facets::detect(text) {
+            let feature = match segment {
+                Segment::Link(uri) => Feature::Link { uri },
+                Segment::Tag(tag) => Feature::Tag { tag },
+                Segment::Mention(handle) => match self.resolve_handle(&handle).await {
+                    Ok(did) => Feature::Mention { did },
+                    Err(e) => {
+                        eprintln!("[BlueSky] Not linking @{}: {}", handle, e);
+                        continue;
+                    }
+                },
+            };
+            facets.push(Facet::new(range, feature));
+        }
+        facets
+    }
+    
+    /// Find a post's record reference and thread root from its bsky.app URL
+    async fn lookup(&self, url: &str) -> Result<ReplyRef, PostError> {
+        let Some(ParsedUrl::BlueSky { actor, rkey }) = reference::parse(url) else {
+            return Err(PostError::new(ErrorKind::Validation, format!("Not a BlueSky post: {}", url)));
+        };
         
         let client = reqwest::Client::new();
-        let response = client
-            .post("https://bsky.social/xrpc/com.atproto.repo.uploadBlob")
-            .header("Authorization", format!("Bearer {}", token))
-            .header("Content-Type", &mime_type)
-            .body(image_bytes.to_vec())
-            .send()
+        
+        let did = if actor.starts_with("did:") {
+            actor
+        } else {
+            self.resolve_handle(&actor).await?
+        };
+        
+        let uri = format!("at://{}/app.bsky.feed.post/{}", did, rkey);
+        eprintln!("[BlueSky] Resolving {}", uri);
+        
+        let response = self
+            .retry
+            .send("BlueSky getPosts", Idempotency::Idempotent, || {
+                Ok(client
+                    .get(format!("{}/xrpc/app.bsky.feed.getPosts", self.appview_url))
+                    .query(&[("uris", uri.as_str())]))
+            })
             .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(PostError::from_status(status, &error_text, Idempotency::Idempotent));
+        }
         
-        if !response.status().is_success() {
+        let post = response
+            .json::<GetPostsResponse>()
+            .await?
+            .posts
+            .into_iter()
+            .next()
+            .ok_or_else(|| PostError::new(ErrorKind::Validation, format!("Post not found: {}", url)))?;
+        
+        let parent = RemoteId::Record {
+            uri: post.uri,
+            cid: post.cid,
+        };
+        // Replying to a reply keeps the original thread root
+        let root = match post.record.reply {
+            Some(reply) => RemoteId::Record {
+                uri: reply.root.uri,
+                cid: reply.root.cid,
+            },
+            None => parent.clone(),
+        };
+        Ok(ReplyRef { root, parent })
+    }
+    
+    async fn remove(&self, remote_id: &RemoteId) -> Result<PostOutcome, PostError> {
+        let RemoteId::Record { uri, .. } = remote_id else {
+            return Err(PostError::new(ErrorKind::Validation, "Not a BlueSky record"));
+        };
+        let (repo, rkey) = parse_post_uri(uri)
+            .ok_or_else(|| PostError::new(ErrorKind::Validation, format!("Invalid post URI: {}", uri)))?;
+        
+        let mut platform = self.clone();
+        platform.authenticate().await?;
+        
+        let request = DeleteRecordRequest {
+            repo: repo.to_string(),
+            collection: "app.bsky.feed.post".to_string(),
+            rkey: rkey.to_string(),
+        };
+        
+        eprintln!("[BlueSky] Deleting {}", uri);
+        
+        // Deleting a missing record succeeds, so this is safe to repeat
+        let url = format!("{}/xrpc/com.atproto.repo.deleteRecord", platform.service_url);
+        platform
+            .send_refreshing("BlueSky deleteRecord", Idempotency::Idempotent, reqwest::Method::POST, &url, |builder| {
+                Ok(builder.json(&request))
+            })
+            .await?;
+        
+        Ok(PostOutcome {
+            remote_id: Some(remote_id.clone()),
+            ..PostOutcome::success(PlatformId::BlueSky)
+        })
+    }
+    
+    /// Link card for the first URL in a post. A card is a nicety, so any
+    /// failure just posts without one.
+    async fn link_card(&self, text: &str) -> Option<ExternalEmbed> {
+        use crate::image_utils;
+        
+        let url = link_preview::first_url(text)?;
+        let fetcher = LinkPreviewFetcher::new();
+        let preview = match fetcher.fetch(url).await {
+            Ok(preview) => preview,
+            Err(e) => {
+                eprintln!("[BlueSky] No link card for {}: {}", url, e);
+                return None;
+            }
+        };
+        
+        let thumb = if preview.image_url.is_some() {
+            let uploaded = async {
+                let bytes = fetcher.fetch_image(&preview).await
+                    .map_err(|e| PostError::new(ErrorKind::Media, e.to_string()))?;
+                let bytes = image_utils::ImageProcessor::new()
+                    .with_max_size(1_000_000)
+                    .with_max_dimension(2000)
+                    .process(&bytes)
+                    .map_err(|e| PostError::new(ErrorKind::Media, e.to_string()))?;
+                self.upload_blob(&bytes).await
+            };
+            match uploaded.await {
+                Ok(blob_ref) => Some(blob_ref),
+                Err(e) => {
+                    eprintln!("[BlueSky] Link card for {} has no thumbnail: {}", url, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        
+        Some(ExternalEmbed {
+            embed_type: "app.bsky.embed.external".to_string(),
+            external: External {
+                uri: url.to_string(),
+                title: preview.title,
+                description: preview.description,
+                thumb,
+            },
+        })
+    }
+    
+    async fn upload_blob(&self, image_bytes: &[u8]) -> Result<BlobRef, PostError> {
+        use crate::image_utils;
+        
+        let mime_type = image_utils::get_mime_type(image_bytes)
+            .map_err(|e| PostError::new(ErrorKind::Media, e.to_string()))?;
+        
+        // Blobs are content-addressed, so uploading twice is harmless
+        let url = format!("{}/xrpc/com.atproto.repo.uploadBlob", self.service_url);
+        let response = self
+            .send_authorized("BlueSky uploadBlob", Idempotency::Idempotent, reqwest::Method::POST, &url, |builder| {
+                Ok(builder
+                    .header("Content-Type", &mime_type)
+                    .body(image_bytes.to_vec()))
+            })
+            .await?;
+        
+        let status = response.status();
+        if !status.is_success() {
             let error_text = response.text().await?;
-            return Err(anyhow!("Failed to upload blob: {}", error_text));
+            return Err(PostError::from_status(status, &error_text, Idempotency::Idempotent));
         }
         
         let upload_response: UploadBlobResponse = response.json().await?;
@@ -240,13 +989,110 @@ impl BlueSkyPlatform {
     }
 }
 
+/// Combine attached images and a quoted post into the record's embed
+fn embed(images: Vec<ImageRef>, quote: Option<StrongRef>) -> Option<Embed> {
+    let images = (!images.is_empty()).then(|| ImagesEmbed {
+        embed_type: "app.bsky.embed.images".to_string(),
+        images,
+    });
+    let quote = quote.map(|record| RecordEmbed {
+        embed_type: "app.bsky.embed.record".to_string(),
+        record,
+    });
+    
+    match (images, quote) {
+        (None, None) => None,
+        (Some(images), None) => Some(Embed::Images(images)),
+        (None, Some(quote)) => Some(Embed::Record(quote)),
+        (Some(media), Some(record)) => Some(Embed::RecordWithMedia(RecordWithMediaEmbed {
+            embed_type: "app.bsky.embed.recordWithMedia".to_string(),
+            record,
+            media,
+        })),
+    }
+}
+
+/// Split a post record's AT URI (`at://<did>/app.bsky.feed.post/<rkey>`)
+/// into its repo and record key
+fn parse_post_uri(uri: &str) -> Option<(&str, &str)> {
+    let mut parts = uri.strip_prefix("at://")?.split('/');
+    let (did, collection, rkey) = (parts.next()?, parts.next()?, parts.next()?);
+    if collection != "app.bsky.feed.post" || rkey.is_empty() || parts.next().is_some() {
+        return None;
+    }
+    Some((did, rkey))
+}
+
+/// The current OAuth session, with its tokens refreshed if they expired or
+/// are the `rejected` access token
+async fn refresh_oauth(oauth: &OAuthSession, rejected: Option<&str>) -> Result<AtprotoSession, PostError> {
+    let stale = |session: &AtprotoSession| {
+        session.tokens.is_expired() || rejected.is_some_and(|rejected| session.tokens.access_token == rejected)
+    };
+    let mut session = oauth.session.lock().await;
+    
+    if stale(&session) {
+        // Another post may already have refreshed them
+        let stored = Credentials::load()
+            .ok()
+            .and_then(|credentials| credentials.bluesky_oauth)
+            .filter(|stored| stored.did == session.did && stored.dpop_key == session.dpop_key);
+        if let Some(stored) = stored {
+            *session = stored;
+        }
+    }
+    if stale(&session) {
+        eprintln!("[BlueSky] Refreshing OAuth tokens");
+        session.tokens = atproto::refresh(&session)
+            .await
+            .map_err(|e| PostError::new(ErrorKind::Auth, format!("BlueSky sign-in expired, sign in again: {}", e)))?;
+        let refreshed = session.clone();
+        if let Err(e) = Credentials::update(|credentials| credentials.bluesky_oauth = Some(refreshed)) {
+            eprintln!("[BlueSky] Failed to store refreshed tokens: {}", e);
+        }
+    }
+    Ok(session.clone())
+}
+
+/// Whether a JWT's `exp` claim has passed, or will within the safety
+/// margin. Tokens we can't read count as expired.
+fn token_expired(jwt: &str) -> bool {
+    use base64::Engine;
+    
+    #[derive(Deserialize)]
+    struct Claims {
+        exp: i64,
+    }
+    
+    let claims = jwt
+        .split('.')
+        .nth(1)
+        .and_then(|payload| base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok())
+        .and_then(|payload| serde_json::from_slice::<Claims>(&payload).ok());
+    match claims {
+        Some(claims) => claims.exp - TOKEN_EXPIRY_MARGIN_SECS <= chrono::Utc::now().timestamp(),
+        None => true,
+    }
+}
+
+/// Web link for a post record, built from its AT URI
+fn post_url(uri: &str) -> Option<String> {
+    let (did, rkey) = parse_post_uri(uri)?;
+    Some(format!("https://bsky.app/profile/{}/post/{}", did, rkey))
+}
+
 // Make BlueSkyPlatform cloneable for the mut self workaround
 impl Clone for BlueSkyPlatform {
     fn clone(&self) -> Self {
         Self {
+            service_url: self.service_url.clone(),
+            discover_service: self.discover_service,
+            appview_url: self.appview_url.clone(),
             handle: self.handle.clone(),
             app_password: self.app_password.clone(),
-            access_token: self.access_token.clone(),
+            session: self.session.clone(),
+            oauth: self.oauth.clone(),
+            retry: self.retry,
         }
     }
 }
@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: MIT
+
+//! Rich-text facets: clickable links, mentions and hashtags
+//!
+//! Facets point into the post text by UTF-8 byte offsets, which are exactly
+//! Rust string indices.
+
+use crate::link_preview;
+use serde::Serialize;
+use std::ops::Range;
+
+/// Longest hashtag BlueSky accepts, in characters
+const MAX_TAG_LENGTH: usize = 64;
+
+/// `app.bsky.richtext.facet`
+#[derive(Debug, Serialize)]
+pub struct Facet {
+    pub index: ByteSlice,
+    pub features: Vec<Feature>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ByteSlice {
+    pub byte_start: usize,
+    pub byte_end: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "$type")]
+pub enum Feature {
+    #[serde(rename = "app.bsky.richtext.facet#link")]
+    Link { uri: String },
+    #[serde(rename = "app.bsky.richtext.facet#mention")]
+    Mention { did: String },
+    #[serde(rename = "app.bsky.richtext.facet#tag")]
+    Tag { tag: String },
+}
+
+/// Something in the text that should become a facet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Link(String),
+    /// Handle without the leading `@`, still to be resolved to a DID
+    Mention(String),
+    /// Tag without the leading `#`
+    Tag(String),
+}
+
+impl Facet {
+    pub fn new(range: Range<usize>, feature: Feature) -> Self {
+        Self {
+            index: ByteSlice {
+                byte_start: range.start,
+                byte_end: range.end,
+            },
+            features: vec![feature],
+        }
+    }
+}
+
+/// Find links, mentions and hashtags, with the byte range each covers
+pub fn detect(text: &str) -> Vec<(Range<usize>, Segment)> {
+    let mut segments = Vec::new();
+
+    for word in text.split_whitespace() {
+        let mut start = word.as_ptr() as usize - text.as_ptr() as usize;
+        let mut word = word;
+        // Links and mentions may sit in parentheses
+        if let Some(inner) = word.strip_prefix('(') {
+            start += 1;
+            word = inner;
+        }
+
+        if word.starts_with("https://") || word.starts_with("http://") {
+            let url = link_preview::trim_url(word);
+            segments.push((start..start + url.len(), Segment::Link(url.to_string())));
+        } else if let Some(handle) = word.strip_prefix('@') {
+            let handle = handle.trim_end_matches(|c: char| ".,;:!?)'\"".contains(c));
+            if is_handle(handle) {
+                let end = start + 1 + handle.len();
+                segments.push((start..end, Segment::Mention(handle.to_lowercase())));
+            }
+        } else if let Some((prefix, tag)) = ['#', '＃']
+            .iter()
+            .find_map(|prefix| word.strip_prefix(*prefix).map(|tag| (prefix.len_utf8(), tag)))
+        {
+            let tag = tag.trim_end_matches(|c: char| c.is_ascii_punctuation());
+            let valid = !tag.is_empty()
+                && tag.chars().count() <= MAX_TAG_LENGTH
+                && !tag.chars().all(|c| c.is_ascii_digit());
+            if valid {
+                let end = start + prefix + tag.len();
+                segments.push((start..end, Segment::Tag(tag.to_string())));
+            }
+        }
+    }
+
+    segments
+}
+
+/// Whether a string looks like a domain-style handle (`alice.bsky.social`)
+fn is_handle(handle: &str) -> bool {
+    let labels: Vec<&str> = handle.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && labels.last().is_some_and(|tld| tld.starts_with(|c: char| c.is_ascii_alphabetic()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_links_mentions_and_tags() {
+        let text = "Hi @alice.bsky.social, see https://example.com/a. #rust";
+        assert_eq!(
+            detect(text),
+            vec![
+                (3..21, Segment::Mention("alice.bsky.social".to_string())),
+                (27..48, Segment::Link("https://example.com/a".to_string())),
+                (50..55, Segment::Tag("rust".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn offsets_are_bytes_after_non_ascii() {
+        // "Grüße " is 8 bytes but 6 characters
+        let text = "Grüße 🌍 #café https://exämple.de";
+        let segments = detect(text);
+        assert_eq!(segments[0], (13..19, Segment::Tag("café".to_string())));
+        assert_eq!(segments[1], (20..39, Segment::Link("https://exämple.de".to_string())));
+        for (range, _) in &segments {
+            assert!(text.is_char_boundary(range.start) && text.is_char_boundary(range.end));
+        }
+    }
+
+    #[test]
+    fn full_width_hash_starts_a_tag() {
+        let text = "＃タグ";
+        assert_eq!(detect(text), vec![(0..text.len(), Segment::Tag("タグ".to_string()))]);
+    }
+
+    #[test]
+    fn links_in_parentheses() {
+        let text = "(https://en.wikipedia.org/wiki/Rust_(language))";
+        assert_eq!(
+            detect(text),
+            vec![(1..46, Segment::Link("https://en.wikipedia.org/wiki/Rust_(language)".to_string()))]
+        );
+    }
+
+    #[test]
+    fn skips_invalid_handles_and_numeric_tags() {
+        assert!(detect("@alice and #123 and @-bad.com").is_empty());
+    }
+
+    #[test]
+    fn mentions_are_lowercased() {
+        assert_eq!(detect("@Bob.Example.COM")[0].1, Segment::Mention("bob.example.com".to_string()));
+    }
+}
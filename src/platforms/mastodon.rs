@@ -1,6 +1,11 @@
 // SPDX-License-Identifier: MIT
 
-use anyhow::{anyhow, Result};
+use super::retry::{Idempotency, RetryPolicy};
+use super::reference::{self, ParsedUrl};
+use super::{EditMethod, ErrorKind, Platform, PlatformId, Post, PostError, PostImage, PostOutcome, RemoteId, ReplyRef};
+use crate::credentials::Credentials;
+use crate::history::HistoryTarget;
+use futures_util::future::BoxFuture;
 use reqwest::multipart;
 use serde::{Deserialize, Serialize};
 
@@ -8,6 +13,7 @@ use serde::{Deserialize, Serialize};
 pub struct MastodonPlatform {
     instance_url: String,
     access_token: String,
+    retry: RetryPolicy,
 }
 
 #[derive(Debug, Serialize)]
@@ -15,6 +21,8 @@ struct StatusPayload {
     status: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     media_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    in_reply_to_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -22,6 +30,16 @@ struct MediaUploadResponse {
     id: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    statuses: Vec<SearchStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchStatus {
+    id: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct StatusResponse {
     id: String,
@@ -34,26 +52,49 @@ impl MastodonPlatform {
         Self {
             instance_url: instance_url.trim_end_matches('/').to_string(),
             access_token,
+            retry: RetryPolicy::default(),
         }
     }
 
-    pub async fn post(&self, text: String, images: &[Vec<u8>]) -> Result<String> {
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Create the adapter if Mastodon credentials are configured
+    pub fn from_credentials(credentials: &Credentials) -> Option<Self> {
+        if !credentials.has_mastodon() {
+            return None;
+        }
+        Some(Self::new(
+            credentials.mastodon_instance_url.clone()?,
+            credentials.mastodon_access_token.clone()?,
+        ))
+    }
+
+    async fn publish(&self, post: &Post) -> Result<PostOutcome, PostError> {
         eprintln!("[Mastodon] Starting post");
-        eprintln!("[Mastodon] Text length: {}", text.len());
-        eprintln!("[Mastodon] Image count: {}", images.len());
+        eprintln!("[Mastodon] Text length: {}", post.text.len());
+        eprintln!("[Mastodon] Image count: {}", post.images.len());
+
+        let in_reply_to_id = match &post.reply {
+            None => None,
+            Some(ReplyRef { parent: RemoteId::Status(id), .. }) => Some(id.clone()),
+            Some(_) => return Err(PostError::new(ErrorKind::Validation, "Can only reply to a Mastodon status")),
+        };
 
         // Upload images if any
         let mut media_ids = Vec::new();
-        for (i, image_bytes) in images.iter().enumerate() {
-            eprintln!("[Mastodon] Uploading image {} ({} bytes)", i + 1, image_bytes.len());
-            match self.upload_media(image_bytes).await {
+        for (i, image) in post.images.iter().enumerate() {
+            eprintln!("[Mastodon] Uploading image {} ({} bytes)", i + 1, image.bytes.len());
+            match self.upload_media(image).await {
                 Ok(media_id) => {
                     eprintln!("[Mastodon] Image {} uploaded successfully: {}", i + 1, media_id);
                     media_ids.push(media_id);
                 }
                 Err(e) => {
                     eprintln!("[Mastodon] Failed to upload image {}: {}", i + 1, e);
-                    return Err(anyhow!("Failed to upload image: {}", e));
+                    return Err(e.into_media_error());
                 }
             }
         }
@@ -65,19 +106,36 @@ impl MastodonPlatform {
         eprintln!("[Mastodon] Posting to: {}", url);
 
         let payload = StatusPayload {
-            status: text,
+            status: post.text.clone(),
             media_ids: if media_ids.is_empty() {
                 None
             } else {
-                Some(media_ids)
+                Some(media_ids.clone())
             },
+            in_reply_to_id,
         };
 
-        let response = client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.access_token))
-            .json(&payload)
-            .send()
+        // The idempotency key lets the instance drop duplicates, so the status
+        // can be retried safely even if an earlier attempt was committed. It
+        // has to be the same for every retry, so it needs a delivery id.
+        let idempotency_key = post.delivery_id.as_deref().map(|delivery_id| idempotency_key(delivery_id, &payload));
+        let idempotency = if idempotency_key.is_some() {
+            Idempotency::Idempotent
+        } else {
+            Idempotency::NonIdempotent
+        };
+        let response = self
+            .retry
+            .send("Mastodon status", idempotency, || {
+                let request = client
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", self.access_token))
+                    .json(&payload);
+                Ok(match &idempotency_key {
+                    Some(key) => request.header("Idempotency-Key", key),
+                    None => request,
+                })
+            })
             .await?;
 
         let status_code = response.status();
@@ -86,7 +144,7 @@ impl MastodonPlatform {
         if !status_code.is_success() {
             let error_text = response.text().await?;
             eprintln!("[Mastodon] Error response: {}", error_text);
-            return Err(anyhow!("Failed to post status: {} - {}", status_code, error_text));
+            return Err(PostError::from_status(status_code, &error_text, idempotency));
         }
 
         let status_response: StatusResponse = response.json().await?;
@@ -95,15 +153,156 @@ impl MastodonPlatform {
         });
 
         eprintln!("[Mastodon] Post successful: {}", post_url);
-        Ok(post_url)
+        Ok(PostOutcome {
+            url: Some(post_url),
+            remote_id: Some(RemoteId::Status(status_response.id)),
+            media_ids,
+            ..PostOutcome::success(PlatformId::Mastodon)
+        })
+    }
+
+    async fn update(&self, target: &HistoryTarget, post: &Post) -> Result<PostOutcome, PostError> {
+        let Some(RemoteId::Status(id)) = &target.remote_id else {
+            return Err(PostError::new(ErrorKind::Validation, "Not a Mastodon status"));
+        };
+
+        // New images replace the old attachments, otherwise keep them
+        let mut new_media_ids = Vec::new();
+        for (i, image) in post.images.iter().enumerate() {
+            eprintln!("[Mastodon] Uploading image {} ({} bytes)", i + 1, image.bytes.len());
+            let media_id = self.upload_media(image).await.map_err(PostError::into_media_error)?;
+            new_media_ids.push(media_id);
+        }
+        let media_ids = if post.images.is_empty() { target.media_ids.clone() } else { new_media_ids };
+
+        let client = reqwest::Client::new();
+        let url = format!("{}/api/v1/statuses/{}", self.instance_url, id);
+
+        eprintln!("[Mastodon] Editing: {}", url);
+
+        let payload = StatusPayload {
+            status: post.text.clone(),
+            media_ids: if media_ids.is_empty() {
+                None
+            } else {
+                Some(media_ids.clone())
+            },
+            // Edits can't move a status within a thread
+            in_reply_to_id: None,
+        };
+
+        // Sending the same edit twice leaves the status unchanged
+        let response = self
+            .retry
+            .send("Mastodon edit", Idempotency::Idempotent, || {
+                Ok(client
+                    .put(&url)
+                    .header("Authorization", format!("Bearer {}", self.access_token))
+                    .json(&payload))
+            })
+            .await?;
+
+        let status_code = response.status();
+        eprintln!("[Mastodon] Edit response status: {}", status_code);
+
+        if !status_code.is_success() {
+            let error_text = response.text().await?;
+            return Err(PostError::from_status(status_code, &error_text, Idempotency::Idempotent));
+        }
+
+        let status_response: StatusResponse = response.json().await?;
+        let post_url = status_response.url.unwrap_or_else(|| {
+            format!("{}/web/statuses/{}", self.instance_url, status_response.id)
+        });
+
+        Ok(PostOutcome {
+            url: Some(post_url),
+            remote_id: Some(RemoteId::Status(status_response.id)),
+            media_ids,
+            ..PostOutcome::success(PlatformId::Mastodon)
+        })
+    }
+
+    /// Map a status URL from any instance to its id on ours
+    async fn lookup(&self, url: &str) -> Result<ReplyRef, PostError> {
+        let Some(ParsedUrl::Mastodon { url }) = reference::parse(url) else {
+            return Err(PostError::new(ErrorKind::Validation, format!("Not a Mastodon post: {}", url)));
+        };
+
+        let client = reqwest::Client::new();
+        let search_url = format!("{}/api/v2/search", self.instance_url);
+
+        eprintln!("[Mastodon] Resolving: {}", url);
+
+        let response = self
+            .retry
+            .send("Mastodon search", Idempotency::Idempotent, || {
+                Ok(client
+                    .get(&search_url)
+                    .header("Authorization", format!("Bearer {}", self.access_token))
+                    .query(&[("q", url.as_str()), ("type", "statuses"), ("resolve", "true"), ("limit", "1")]))
+            })
+            .await?;
+
+        let status_code = response.status();
+        if !status_code.is_success() {
+            let error_text = response.text().await?;
+            return Err(PostError::from_status(status_code, &error_text, Idempotency::Idempotent));
+        }
+
+        let search: SearchResponse = response.json().await?;
+        let status = search.statuses.into_iter().next().ok_or_else(|| {
+            PostError::new(ErrorKind::Validation, format!("Status not found: {}", url))
+        })?;
+
+        // Replies only need the parent on Mastodon
+        Ok(ReplyRef {
+            root: RemoteId::Status(status.id.clone()),
+            parent: RemoteId::Status(status.id),
+        })
+    }
+
+    async fn remove(&self, remote_id: &RemoteId) -> Result<PostOutcome, PostError> {
+        let RemoteId::Status(id) = remote_id else {
+            return Err(PostError::new(ErrorKind::Validation, "Not a Mastodon status"));
+        };
+
+        let client = reqwest::Client::new();
+        let url = format!("{}/api/v1/statuses/{}", self.instance_url, id);
+
+        eprintln!("[Mastodon] Deleting: {}", url);
+
+        let response = self
+            .retry
+            .send("Mastodon delete", Idempotency::Idempotent, || {
+                Ok(client
+                    .delete(&url)
+                    .header("Authorization", format!("Bearer {}", self.access_token)))
+            })
+            .await?;
+
+        let status_code = response.status();
+        eprintln!("[Mastodon] Delete response status: {}", status_code);
+
+        if !status_code.is_success() && status_code != reqwest::StatusCode::NOT_FOUND {
+            let error_text = response.text().await?;
+            return Err(PostError::from_status(status_code, &error_text, Idempotency::Idempotent));
+        }
+
+        Ok(PostOutcome {
+            remote_id: Some(remote_id.clone()),
+            ..PostOutcome::success(PlatformId::Mastodon)
+        })
     }
 
-    async fn upload_media(&self, image_bytes: &[u8]) -> Result<String> {
+    async fn upload_media(&self, image: &PostImage) -> Result<String, PostError> {
         use crate::image_utils;
         
+        let image_bytes = image.bytes.as_slice();
         eprintln!("[Mastodon] Processing image ({} bytes)", image_bytes.len());
         
-        let mime_type = image_utils::get_mime_type(image_bytes)?;
+        let mime_type = image_utils::get_mime_type(image_bytes)
+            .map_err(|e| PostError::new(ErrorKind::Media, e.to_string()))?;
         let file_name = format!("image.{}", 
             if mime_type.contains("png") { "png" } 
             else if mime_type.contains("gif") { "gif" }
@@ -113,22 +312,27 @@ impl MastodonPlatform {
 
         eprintln!("[Mastodon] MIME type: {}, filename: {}", mime_type, file_name);
 
-        let part = multipart::Part::bytes(image_bytes.to_vec())
-            .file_name(file_name)
-            .mime_str(&mime_type)?;
-
-        let form = multipart::Form::new().part("file", part);
-
         let client = reqwest::Client::new();
         let url = format!("{}/api/v2/media", self.instance_url);
 
         eprintln!("[Mastodon] Uploading to: {}", url);
 
-        let response = client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.access_token))
-            .multipart(form)
-            .send()
+        let response = self
+            .retry
+            .send("Mastodon media upload", Idempotency::Idempotent, || {
+                let part = multipart::Part::bytes(image_bytes.to_vec())
+                    .file_name(file_name.clone())
+                    .mime_str(&mime_type)?;
+                let mut form = multipart::Form::new().part("file", part);
+                if !image.alt.is_empty() {
+                    form = form.text("description", image.alt.clone());
+                }
+
+                Ok(client
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", self.access_token))
+                    .multipart(form))
+            })
             .await?;
 
         let status_code = response.status();
@@ -137,7 +341,7 @@ impl MastodonPlatform {
         if !status_code.is_success() {
             let error_text = response.text().await?;
             eprintln!("[Mastodon] Upload error: {}", error_text);
-            return Err(anyhow!("Failed to upload media: {} - {}", status_code, error_text));
+            return Err(PostError::from_status(status_code, &error_text, Idempotency::Idempotent));
         }
 
         let media_response: MediaUploadResponse = response.json().await?;
@@ -146,3 +350,91 @@ impl MastodonPlatform {
         Ok(media_response.id)
     }
 }
+
+/// Derive an `Idempotency-Key` for a status of a delivery. Every retry of
+/// the delivery gets the same key, while each part of a thread gets its own.
+/// Media ids are left out since images are uploaded again on each retry.
+fn idempotency_key(delivery_id: &str, payload: &StatusPayload) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(delivery_id.as_bytes());
+    hasher.update([0]);
+    hasher.update(payload.status.as_bytes());
+    if let Some(in_reply_to_id) = &payload.in_reply_to_id {
+        hasher.update([0]);
+        hasher.update(in_reply_to_id.as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+impl Platform for MastodonPlatform {
+    fn id(&self) -> PlatformId {
+        PlatformId::Mastodon
+    }
+
+    fn is_authenticated(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async move { !self.instance_url.is_empty() && !self.access_token.is_empty() })
+    }
+
+    fn post<'a>(&'a self, post: &'a Post) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+        Box::pin(self.publish(post))
+    }
+
+    fn delete<'a>(&'a self, remote_id: &'a RemoteId) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+        Box::pin(self.remove(remote_id))
+    }
+
+    fn resolve<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<ReplyRef, PostError>> {
+        Box::pin(self.lookup(url))
+    }
+
+    fn supports_quotes(&self) -> bool {
+        false
+    }
+
+    fn edit_method(&self) -> EditMethod {
+        EditMethod::InPlace
+    }
+
+    fn edit<'a>(
+        &'a self,
+        target: &'a HistoryTarget,
+        post: &'a Post,
+    ) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+        Box::pin(self.update(target, post))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(status: &str, in_reply_to_id: Option<&str>) -> StatusPayload {
+        StatusPayload {
+            status: status.to_string(),
+            media_ids: None,
+            in_reply_to_id: in_reply_to_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn idempotency_key_is_stable_across_retries() {
+        let first = StatusPayload {
+            media_ids: Some(vec!["1".to_string()]),
+            ..payload("Hello", None)
+        };
+        let retry = StatusPayload {
+            media_ids: Some(vec!["2".to_string()]),
+            ..payload("Hello", None)
+        };
+        assert_eq!(idempotency_key("d1", &first), idempotency_key("d1", &retry));
+    }
+
+    #[test]
+    fn idempotency_key_differs_per_delivery_and_part() {
+        let key = idempotency_key("d1", &payload("Hello", None));
+        assert_ne!(key, idempotency_key("d2", &payload("Hello", None)));
+        assert_ne!(key, idempotency_key("d1", &payload("Hello", Some("42"))));
+    }
+}
@@ -2,35 +2,406 @@
 
 //! Platform adapters for social media services
 
-use anyhow::Result;
+use crate::history::HistoryTarget;
+use futures_util::future::BoxFuture;
+use retry::Idempotency;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
 pub mod nostr;
 pub mod bluesky;
 pub mod twitter;
 pub mod mastodon;
+pub mod reference;
+mod registry;
+pub mod retry;
+
+pub use reference::{PostReference, ReferenceKind};
+pub use registry::PlatformRegistry;
 
 /// Represents a post with text and optional images
 #[derive(Debug, Clone)]
 pub struct Post {
     pub text: String,
-    pub images: Vec<Vec<u8>>,
+    pub images: Vec<PostImage>,
+    /// Post this one replies to
+    pub reply: Option<ReplyRef>,
+    /// Post this one quotes
+    pub quote: Option<RemoteId>,
+    /// Post to reply to or quote, by URL. The platform it belongs to turns it
+    /// into `reply`/`quote`; the others get the link appended.
+    pub reference: Option<PostReference>,
+    /// Split the text into a reply chain if it is too long for a platform
+    pub thread: Option<ThreadOptions>,
+    /// Identifies this delivery across every retry of it, so platforms that
+    /// deduplicate requests can recognise a repeat
+    pub delivery_id: Option<String>,
+}
+
+impl Post {
+    pub fn new(text: String, images: Vec<PostImage>) -> Self {
+        Self {
+            text,
+            images,
+            reply: None,
+            quote: None,
+            reference: None,
+            thread: None,
+            delivery_id: None,
+        }
+    }
+}
+
+/// An attached image and its description for screen readers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostImage {
+    pub bytes: Vec<u8>,
+    /// Alt text; empty if the user gave none
+    pub alt: String,
+}
+
+impl PostImage {
+    pub fn new(bytes: Vec<u8>, alt: impl Into<String>) -> Self {
+        Self {
+            bytes,
+            alt: alt.into().trim().to_string(),
+        }
+    }
+}
+
+/// Where a reply sits in a thread, as ids on the platform being posted to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyRef {
+    /// First post of the thread
+    pub root: RemoteId,
+    /// Post being replied to directly
+    pub parent: RemoteId,
+}
+
+/// How long text is split into a thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ThreadOptions {
+    /// Append "1/3"-style counters to each part
+    pub numbered: bool,
+}
+
+/// Identifies a supported platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PlatformId {
+    X,
+    BlueSky,
+    Nostr,
+    Mastodon,
 }
 
-/// Result of posting to a platform
+impl PlatformId {
+    /// All supported platforms, in display order
+    pub const ALL: [PlatformId; 4] = [
+        PlatformId::X,
+        PlatformId::BlueSky,
+        PlatformId::Nostr,
+        PlatformId::Mastodon,
+    ];
+    
+    /// Display name of the platform
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlatformId::X => "X",
+            PlatformId::BlueSky => "BlueSky",
+            PlatformId::Nostr => "Nostr",
+            PlatformId::Mastodon => "Mastodon",
+        }
+    }
+}
+
+impl fmt::Display for PlatformId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Identifier of a published post on its platform
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RemoteId {
+    /// Mastodon status id
+    Status(String),
+    /// AT Protocol record reference
+    Record { uri: String, cid: String },
+    /// X tweet id
+    Tweet(String),
+    /// Nostr event id (hex)
+    Event(String),
+}
+
+/// How a platform applies an edit to a published post
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditMethod {
+    /// The post is changed where it is, keeping its link and replies
+    InPlace,
+    /// A new post replaces the original, which is then deleted
+    Repost,
+}
+
+/// Whether a single Nostr relay accepted an event
 #[derive(Debug, Clone)]
-pub enum PostResult {
-    Success { url: Option<String> },
-    Error { message: String },
+pub struct RelayStatus {
+    pub url: String,
+    pub accepted: bool,
+    pub message: Option<String>,
+}
+
+/// Classification of a posting failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Credentials missing, invalid or rejected
+    Auth,
+    /// The platform asked us to slow down
+    RateLimit,
+    /// The platform rejected the content itself
+    Validation,
+    /// Connection problems or server-side failures
+    Network,
+    /// Image processing or upload failed
+    Media,
+    /// The platform did not answer in time
+    Timeout,
+    /// The platform has no credentials configured
+    NotConfigured,
+    /// The user stopped the post before it was sent
+    Cancelled,
+    Other,
+}
+
+impl ErrorKind {
+    /// Whether trying again later has a reasonable chance of succeeding
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ErrorKind::Network | ErrorKind::RateLimit | ErrorKind::Timeout)
+    }
+    
+    /// Classify an unsuccessful HTTP status code
+    pub fn from_status(status: reqwest::StatusCode) -> Self {
+        match status.as_u16() {
+            401 | 403 => ErrorKind::Auth,
+            429 => ErrorKind::RateLimit,
+            400 | 404 | 413 | 415 | 422 => ErrorKind::Validation,
+            408 => ErrorKind::Timeout,
+            500..=599 => ErrorKind::Network,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::Auth => "authentication failed",
+            ErrorKind::RateLimit => "rate limited",
+            ErrorKind::Validation => "rejected",
+            ErrorKind::Network => "network error",
+            ErrorKind::Media => "media error",
+            ErrorKind::Timeout => "timed out",
+            ErrorKind::NotConfigured => "not configured",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::Other => "error",
+        })
+    }
+}
+
+/// A classified posting error
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{message}")]
+pub struct PostError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// The request is known not to have taken effect, because it never
+    /// reached the server or was refused before doing anything. Only then
+    /// can it be sent again without risking a duplicate post.
+    pub retry_safe: bool,
+}
+
+impl PostError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            retry_safe: false,
+        }
+    }
+    
+    pub fn with_retry_safe(mut self, retry_safe: bool) -> Self {
+        self.retry_safe = retry_safe;
+        self
+    }
+    
+    /// Build an error from an unsuccessful HTTP response status and body.
+    /// `idempotency` is that of the request, which decides whether a server
+    /// error may have left the request half done.
+    pub fn from_status(status: reqwest::StatusCode, body: &str, idempotency: Idempotency) -> Self {
+        Self::new(ErrorKind::from_status(status), format!("{} - {}", status, body))
+            .with_retry_safe(retry::is_retryable_status(status, idempotency))
+    }
+    
+    /// Mark an error from an image processing/upload step as a media error,
+    /// keeping auth, rate-limit and network classifications intact
+    pub fn into_media_error(self) -> Self {
+        let kind = match self.kind {
+            ErrorKind::Validation | ErrorKind::Other => ErrorKind::Media,
+            kind => kind,
+        };
+        Self::new(kind, format!("Failed to upload image: {}", self.message)).with_retry_safe(self.retry_safe)
+    }
+}
+
+impl From<reqwest::Error> for PostError {
+    fn from(e: reqwest::Error) -> Self {
+        let kind = if e.is_timeout() {
+            ErrorKind::Timeout
+        } else if e.is_decode() {
+            ErrorKind::Other
+        } else if let Some(status) = e.status() {
+            ErrorKind::from_status(status)
+        } else {
+            ErrorKind::Network
+        };
+        // Only a failed connection is sure not to have reached the server
+        Self::new(kind, e.to_string()).with_retry_safe(e.is_connect())
+    }
+}
+
+/// Result of posting to a single platform
+#[derive(Debug, Clone)]
+pub struct PostOutcome {
+    pub platform: PlatformId,
+    /// Canonical web link to the post, if the platform has one
+    pub url: Option<String>,
+    /// Platform-specific id of the post
+    pub remote_id: Option<RemoteId>,
+    /// Ids of uploaded media attachments
+    pub media_ids: Vec<String>,
+    /// Per-relay acceptance (Nostr only)
+    pub relays: Vec<RelayStatus>,
+    /// Ids of the follow-up posts when the text was posted as a thread
+    pub thread_ids: Vec<RemoteId>,
+    /// Part of the post went out before `error`, so it can't be sent again
+    /// without duplicating what was published
+    pub partial: bool,
+    pub error: Option<PostError>,
+}
+
+impl PostOutcome {
+    pub fn success(platform: PlatformId) -> Self {
+        Self {
+            platform,
+            url: None,
+            remote_id: None,
+            media_ids: Vec::new(),
+            relays: Vec::new(),
+            thread_ids: Vec::new(),
+            partial: false,
+            error: None,
+        }
+    }
+    
+    pub fn failure(platform: PlatformId, error: PostError) -> Self {
+        Self {
+            error: Some(error),
+            ..Self::success(platform)
+        }
+    }
+    
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+    
+    /// How many relays took the post and why the others didn't, e.g.
+    /// "2/3 relays, rejected by wss://relay.example (pow: difficulty 20)"
+    pub fn relay_summary(&self) -> Option<String> {
+        if self.relays.is_empty() {
+            return None;
+        }
+        let accepted = self.relays.iter().filter(|relay| relay.accepted).count();
+        let mut summary = format!("{}/{} relays", accepted, self.relays.len());
+        let rejected: Vec<String> = self
+            .relays
+            .iter()
+            .filter(|relay| !relay.accepted)
+            .map(|relay| match &relay.message {
+                Some(message) => format!("{} ({})", relay.url, message),
+                None => relay.url.clone(),
+            })
+            .collect();
+        if !rejected.is_empty() {
+            summary.push_str(&format!(", rejected by {}", rejected.join(", ")));
+        }
+        Some(summary)
+    }
 }
 
 /// Abstract platform adapter trait
+///
+/// The trait is object safe so adapters can be stored in a [`PlatformRegistry`].
 pub trait Platform: Send + Sync {
-    /// Get the platform name
-    fn name(&self) -> &'static str;
+    /// Get the platform id
+    fn id(&self) -> PlatformId;
     
     /// Check if the platform is authenticated/ready
-    fn is_authenticated(&self) -> impl std::future::Future<Output = bool> + Send;
+    fn is_authenticated(&self) -> BoxFuture<'_, bool>;
     
     /// Post content to the platform
-    fn post(&self, post: &Post) -> impl std::future::Future<Output = Result<PostResult>> + Send;
+    fn post<'a>(&'a self, post: &'a Post) -> BoxFuture<'a, Result<PostOutcome, PostError>>;
+    
+    /// Delete a previously published post. A post that is already gone
+    /// counts as deleted.
+    fn delete<'a>(&'a self, remote_id: &'a RemoteId) -> BoxFuture<'a, Result<PostOutcome, PostError>>;
+    
+    /// Look up a post on this platform by its web URL
+    fn resolve<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<ReplyRef, PostError>>;
+    
+    /// Whether [`Post::quote`] is supported; otherwise quotes become links
+    fn supports_quotes(&self) -> bool {
+        true
+    }
+    
+    /// How [`Platform::edit`] changes a post on this platform
+    fn edit_method(&self) -> EditMethod {
+        EditMethod::Repost
+    }
+    
+    /// Replace a published post with new content. The target's media ids
+    /// are the original attachments, kept by in-place edits when no new
+    /// images are given.
+    ///
+    /// The default reposts: the replacement is published first, so a failed
+    /// delete leaves a duplicate rather than losing the post. The original's
+    /// thread parts are deleted along with it, and a failed delete comes back
+    /// as a partial outcome.
+    fn edit<'a>(
+        &'a self,
+        target: &'a HistoryTarget,
+        post: &'a Post,
+    ) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+        Box::pin(async move {
+            let Some(remote_id) = &target.remote_id else {
+                return Err(PostError::new(ErrorKind::Validation, "The post has no id to replace"));
+            };
+            let mut outcome = self.post(post).await?;
+            
+            // Replies first, so a thread is never left without its start
+            let mut failures = Vec::new();
+            for id in target.thread_ids.iter().rev().chain(std::iter::once(remote_id)) {
+                if let Err(e) = self.delete(id).await {
+                    failures.push(e);
+                }
+            }
+            if let Some(e) = failures.pop() {
+                eprintln!("[{}] Reposted, but failed to delete the original: {}", self.id(), e);
+                outcome.error = Some(PostError::new(
+                    e.kind,
+                    format!("Reposted, but failed to delete the original: {}", e.message),
+                ));
+                outcome.partial = true;
+            }
+            Ok(outcome)
+        })
+    }
 }
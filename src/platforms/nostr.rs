@@ -1,23 +1,93 @@
 // SPDX-License-Identifier: MIT
 
-//! Nostr platform adapter with dual authentication support
+//! Nostr platform adapter, signing with an nsec key, Pleb_Signer or a NIP-46
+//! remote signer
 
-use super::{Platform, Post, PostResult};
+mod pow;
+mod publisher;
+mod relay_list;
+mod signer;
+
+use super::reference::{self, ParsedUrl};
+use super::retry::{Idempotency, RetryPolicy};
+use super::{ErrorKind, Platform, PlatformId, Post, PostError, PostOutcome, RelayStatus, RemoteId, ReplyRef};
+use crate::credentials::Credentials;
 use anyhow::{Result, anyhow};
+use futures_util::future::BoxFuture;
+use nostr_connect::prelude::NostrConnect;
 use nostr_sdk::prelude::*;
 use serde::Deserialize;
+use signer::{BunkerSigner, LocalSigner, NostrSigner, PlebSigner};
+use std::fmt::Display;
+use std::time::Duration;
+
+pub use pow::PowProgress;
+
+/// Web viewer used for event links unless configured otherwise
+pub const DEFAULT_VIEWER_URL: &str = "https://njump.me";
+
+/// Relays used unless configured otherwise
+pub const DEFAULT_RELAYS: &[&str] = &["wss://relay.primal.net", "wss://relay.damus.io", "wss://relay.pleb.one"];
+
+/// How long the relays for looking up relay lists get to connect
+const RELAY_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long relays get to return the event being replied to
+const PARENT_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of relay hints embedded in an `nevent` link
+const MAX_RELAY_HINTS: usize = 3;
+
+/// How long a remote signer gets to answer, which includes the user
+/// approving the request on their device
+const BUNKER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long we wait for the user to paste a `nostrconnect://` link into
+/// their signer
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(300);
 
 pub enum NostrAuth {
     /// Direct private key
     Nsec(String),
     /// Use Pleb_Signer via D-Bus
     PlebSigner,
+    /// NIP-46 remote signer
+    Bunker {
+        /// `bunker://` or `nostrconnect://` URI
+        uri: String,
+        /// Secret key that identifies doh to the signer
+        app_key: String,
+    },
 }
 
 pub struct NostrPlatform {
     auth: NostrAuth,
     relays: Vec<String>,
     image_host_url: Option<String>,
+    viewer_url: String,
+    /// Also publish a NIP-94 file metadata event for each uploaded image
+    file_metadata: bool,
+    /// Proof-of-work difficulty for notes (NIP-13), 0 for none
+    pow_difficulty: u8,
+    pow_progress: PowProgress,
+    /// Backend for `auth`, set up on first use
+    signer: tokio::sync::OnceCell<Box<dyn NostrSigner>>,
+    retry: RetryPolicy,
+}
+
+/// An image stored on the Blossom server and what clients want to know
+/// about it before downloading it
+struct UploadedImage {
+    url: String,
+    mime_type: String,
+    /// Hex SHA-256 of the uploaded bytes
+    sha256: String,
+    /// Hex SHA-256 of the image before EXIF stripping
+    original_sha256: String,
+    size: usize,
+    dimensions: Option<(u32, u32)>,
+    blurhash: Option<String>,
+    alt: String,
 }
 
 impl NostrPlatform {
@@ -26,65 +96,130 @@ impl NostrPlatform {
             auth,
             relays,
             image_host_url,
+            viewer_url: DEFAULT_VIEWER_URL.to_string(),
+            file_metadata: false,
+            pow_difficulty: 0,
+            pow_progress: PowProgress::default(),
+            signer: tokio::sync::OnceCell::new(),
+            retry: RetryPolicy::default(),
         }
     }
     
-    async fn get_keys(&self) -> Result<Keys> {
-        match &self.auth {
-            NostrAuth::Nsec(nsec_str) => {
-                eprintln!("[Nostr] Parsing nsec key (length: {})", nsec_str.len());
-                if nsec_str.is_empty() {
-                    eprintln!("[Nostr] Error: nsec key is empty!");
-                    return Err(anyhow!("nsec key is empty"));
-                }
-                
-                // Try to parse as nsec
-                match Keys::parse(nsec_str) {
-                    Ok(keys) => {
-                        eprintln!("[Nostr] Successfully parsed nsec key");
-                        Ok(keys)
-                    }
-                    Err(e) => {
-                        eprintln!("[Nostr] Failed to parse nsec key: {}", e);
-                        eprintln!("[Nostr] Hint: nsec should start with 'nsec1'");
-                        Err(anyhow!("Invalid nsec key: {}. Must start with 'nsec1'", e))
-                    }
-                }
-            }
-            NostrAuth::PlebSigner => {
-                eprintln!("[Nostr] Using Pleb_Signer for authentication");
-                
-                // For Pleb_Signer, we can't get Keys directly
-                // We'll need to create a dummy Keys object for the client
-                // The actual signing will be done by Pleb_Signer
-                
-                // Just generate a temporary key for the client
-                // (we won't use it for signing)
-                Ok(Keys::generate())
-            }
-        }
+    /// Link published events to a different web viewer
+    pub fn with_viewer_url(mut self, viewer_url: String) -> Self {
+        self.viewer_url = viewer_url.trim_end_matches('/').to_string();
+        self
     }
     
-    async fn post_with_nsec(&self, post: &Post) -> Result<PostResult> {
-        use crate::image_utils;
+    /// Publish a NIP-94 file metadata event (kind 1063) for each image
+    pub fn with_file_metadata(mut self, file_metadata: bool) -> Self {
+        self.file_metadata = file_metadata;
+        self
+    }
+    
+    /// Mine each note until its id has `difficulty` leading zero bits
+    pub fn with_pow_difficulty(mut self, difficulty: u8) -> Self {
+        self.pow_difficulty = difficulty;
+        self
+    }
+    
+    /// Report mining progress to `progress`, which can also cancel it
+    pub fn with_pow_progress(mut self, progress: PowProgress) -> Self {
+        self.pow_progress = progress;
+        self
+    }
+    
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    
+    /// Create the adapter if a Nostr key or Pleb_Signer is configured
+    pub fn from_credentials(credentials: &Credentials) -> Option<Self> {
+        if !credentials.has_nostr() {
+            return None;
+        }
         
-        // Get keys
-        let keys = match self.get_keys().await {
-            Ok(k) => {
-                eprintln!("[Nostr] Keys loaded successfully");
-                k
-            }
-            Err(e) => {
-                eprintln!("[Nostr] Failed to load keys: {}", e);
-                return Ok(PostResult::Error { 
-                    message: format!("Failed to load keys: {}", e)
-                });
+        let bunker_uri = credentials
+            .nostr_bunker_uri
+            .as_deref()
+            .map(str::trim)
+            .filter(|uri| !uri.is_empty())
+            .map(str::to_string);
+        let auth = if credentials.nostr_use_pleb_signer {
+            NostrAuth::PlebSigner
+        } else if let Some(uri) = bunker_uri {
+            NostrAuth::Bunker {
+                uri,
+                app_key: credentials.nostr_bunker_app_key.clone().unwrap_or_default(),
             }
+        } else {
+            NostrAuth::Nsec(credentials.nostr_nsec.clone().unwrap_or_default())
         };
         
-        // Create client
-        eprintln!("[Nostr] Creating client...");
-        let client = Client::new(keys);
+        let relays = if credentials.nostr_relays.is_empty() {
+            DEFAULT_RELAYS.iter().map(|relay| relay.to_string()).collect()
+        } else {
+            credentials.nostr_relays.clone()
+        };
+        
+        let platform = Self::new(auth, relays, credentials.nostr_image_host_url.clone())
+            .with_file_metadata(credentials.nostr_file_metadata)
+            .with_pow_difficulty(credentials.nostr_pow_difficulty);
+        Some(match credentials.nostr_viewer_url.clone().filter(|url| !url.is_empty()) {
+            Some(viewer_url) => platform.with_viewer_url(viewer_url),
+            None => platform,
+        })
+    }
+    
+    /// The configured signer
+    async fn signer(&self) -> Result<&dyn NostrSigner, PostError> {
+        let signer = self
+            .signer
+            .get_or_try_init(|| async {
+                let signer: Box<dyn NostrSigner> = match &self.auth {
+                    NostrAuth::Nsec(nsec) => Box::new(LocalSigner::parse(nsec)?),
+                    NostrAuth::PlebSigner => {
+                        eprintln!("[Nostr] Using Pleb_Signer for authentication");
+                        Box::new(PlebSigner)
+                    }
+                    NostrAuth::Bunker { uri, app_key } => {
+                        eprintln!("[Nostr] Using remote signer for authentication");
+                        Box::new(BunkerSigner::new(uri, app_key, BUNKER_TIMEOUT)?)
+                    }
+                };
+                Ok::<_, PostError>(signer)
+            })
+            .await?;
+        Ok(signer.as_ref())
+    }
+    
+    /// Sign an event as the user with whichever signer is configured
+    async fn sign(&self, builder: EventBuilder) -> Result<Event, PostError> {
+        let signer = self.signer().await?;
+        let unsigned = builder.build(signer.get_public_key().await?);
+        signer.sign_event(unsigned).await
+    }
+    
+    /// Sign a note, mining proof of work into it first if configured. The
+    /// work is done before any signer sees the event, Pleb_Signer included.
+    async fn sign_note(&self, builder: EventBuilder) -> Result<Event, PostError> {
+        let signer = self.signer().await?;
+        let mut unsigned = builder.build(signer.get_public_key().await?);
+        if self.pow_difficulty > 0 {
+            unsigned = pow::mine(unsigned, self.pow_difficulty, &self.pow_progress).await?;
+        }
+        let event = signer.sign_event(unsigned).await?;
+        
+        if pow::leading_zero_bits(event.id.as_bytes()) < u32::from(self.pow_difficulty) {
+            eprintln!("[Nostr] Warning: signed event {} doesn't meet difficulty {}", event.id, self.pow_difficulty);
+        }
+        Ok(event)
+    }
+    
+    /// Create a client for looking up relay lists on the configured relays
+    async fn lookup_client(&self) -> Client {
+        let client = Client::default();
         
         // Add relays
         eprintln!("[Nostr] Adding {} relays...", self.relays.len());
@@ -97,81 +232,175 @@ impl NostrPlatform {
         }
         
         eprintln!("[Nostr] Connecting to relays...");
-        client.connect().await;
-        
-        // Give relays a moment to connect
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-        
-        // Upload images if any
-        let mut image_urls = Vec::new();
-        if !post.images.is_empty() {
-            if let Some(blossom_url) = &self.image_host_url {
-                eprintln!("[Nostr] Uploading {} images to Blossom", post.images.len());
-                for (i, image_bytes) in post.images.iter().enumerate() {
-                    // Strip EXIF data for privacy (Blossom requirement)
-                    let processor = image_utils::ImageProcessor::new()
-                        .with_exif_stripping();
-                    
-                    let processed_bytes = match processor.process(image_bytes) {
-                        Ok(bytes) => {
-                            eprintln!("[Nostr] Image {} processed ({} -> {} bytes)", 
-                                i + 1, image_bytes.len(), bytes.len());
-                            bytes
-                        }
-                        Err(e) => {
-                            eprintln!("[Nostr] Failed to process image {}: {}", i + 1, e);
-                            continue;
-                        }
-                    };
-                    
-                    match self.upload_to_blossom(&processed_bytes, blossom_url, &client).await {
-                        Ok(url) => {
-                            eprintln!("[Nostr] Image {} uploaded: {}", i + 1, url);
-                            image_urls.push(url);
-                        }
-                        Err(e) => {
-                            eprintln!("[Nostr] Failed to upload image {}: {}", i + 1, e);
-                        }
-                    }
-                }
-            } else {
-                eprintln!("[Nostr] No Blossom server configured, skipping image upload");
-            }
-        }
+        client.connect_with_timeout(RELAY_CONNECT_TIMEOUT).await;
+        client
+    }
+    
+    /// Send a signed event to `relays`, answering AUTH challenges (NIP-42)
+    /// with the configured signer. Fails if no relay accepted it.
+    async fn send_event(&self, relays: &[String], event: &Event) -> Result<Vec<RelayStatus>, PostError> {
+        let signer = self.signer().await?;
+        eprintln!("[Nostr] Sending event to {} relays...", relays.len());
+        check_relays(publisher::send(signer, relays, event).await)
+    }
+    
+    async fn publish(&self, post: &Post) -> Result<PostOutcome, PostError> {
+        // Make sure the signer has a key before uploading anything
+        let author = self.signer().await?.get_public_key().await?;
+        
+        let images = self.upload_images(post).await;
         
         // Create text note with image URLs
         let mut content = post.text.clone();
-        for url in &image_urls {
-            content.push_str(&format!("\n{}", url));
+        for image in &images {
+            content.push_str(&format!("\n{}", image.url));
         }
+        append_quote(&mut content, post)?;
         
         eprintln!("[Nostr] Creating event...");
-        let builder = EventBuilder::text_note(&content, vec![]);
-        
-        eprintln!("[Nostr] Sending event to relays...");
-        match client.send_event_builder(builder).await {
-            Ok(event_id) => {
-                eprintln!("[Nostr] Event sent successfully: {:?}", event_id);
-                Ok(PostResult::Success { url: None })
+        let mut mentions = mentioned_pubkeys(&post.text);
+        for pubkey in self.reply_pubkeys(post).await {
+            if pubkey != author && !mentions.contains(&pubkey) {
+                mentions.push(pubkey);
+            }
+        }
+        let mut tags = reference_tags(post)?;
+        tags.extend(mentions.iter().map(|pubkey| vec!["p".to_string(), pubkey.to_hex()]));
+        tags.extend(images.iter().map(UploadedImage::imeta_tag));
+        let event = self.sign_note(EventBuilder::text_note(&content, custom_tags(tags))).await?;
+        
+        let relays = self.outbox_relays(author, &mentions).await;
+        
+        if self.file_metadata {
+            for image in &images {
+                let builder = EventBuilder::new(Kind::FileMetadata, &image.alt, custom_tags(image.file_metadata_tags()));
+                let sent = match self.sign(builder).await {
+                    Ok(event) => self.send_event(&relays, &event).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = sent {
+                    eprintln!("[Nostr] Failed to publish file metadata for {}: {}", image.url, e);
+                }
             }
+        }
+        
+        let statuses = self.send_event(&relays, &event).await?;
+        Ok(event_outcome(&event.id, author, &self.viewer_url, statuses))
+    }
+    
+    /// Relays to publish to under the outbox model (NIP-65). The configured
+    /// relays are used to look up relay lists and stand in for an author who
+    /// has none.
+    async fn outbox_relays(&self, author: PublicKey, mentions: &[PublicKey]) -> Vec<String> {
+        let client = self.lookup_client().await;
+        let authors = std::iter::once(author).chain(mentions.iter().copied()).collect();
+        let lists = relay_list::fetch(&client, authors).await;
+        let relays = relay_list::targets(&author, mentions, &lists, &self.relays);
+        eprintln!("[Nostr] Publishing to relays: {:?}", relays);
+        relays
+    }
+    
+    /// Who a reply tags (NIP-10): the parent's author and everyone the
+    /// parent tagged. Empty if the parent can't be found on the relays.
+    async fn reply_pubkeys(&self, post: &Post) -> Vec<PublicKey> {
+        let Some(reply) = &post.reply else {
+            return Vec::new();
+        };
+        let Some(parent_id) = event_hex(&reply.parent).ok().and_then(|hex| EventId::from_hex(&hex).ok()) else {
+            return Vec::new();
+        };
+        
+        let client = self.lookup_client().await;
+        let filter = Filter::new().id(parent_id).limit(1);
+        let parent = match client.fetch_events(vec![filter], Some(PARENT_FETCH_TIMEOUT)).await {
+            Ok(events) => events.into_iter().next(),
             Err(e) => {
-                eprintln!("[Nostr] Failed to send event: {}", e);
-                Ok(PostResult::Error {
-                    message: format!("Failed to send event: {}", e)
-                })
+                eprintln!("[Nostr] Failed to fetch {}: {}", parent_id, e);
+                None
+            }
+        };
+        let Some(parent) = parent else {
+            eprintln!("[Nostr] Couldn't find {}, not tagging its author", parent_id);
+            return Vec::new();
+        };
+        
+        let tagged = parent.tags.iter().filter_map(|tag| match tag.as_slice() {
+            [name, hex, ..] if name == "p" => PublicKey::from_hex(hex).ok(),
+            _ => None,
+        });
+        let mut pubkeys = Vec::new();
+        for pubkey in std::iter::once(parent.pubkey).chain(tagged) {
+            if !pubkeys.contains(&pubkey) {
+                pubkeys.push(pubkey);
             }
         }
+        pubkeys
+    }
+    
+    /// Publish the configured relays as the user's relay list (NIP-65), for
+    /// both reading and writing
+    pub async fn publish_relay_list(&self) -> Result<Vec<RelayStatus>, PostError> {
+        let tags = self
+            .relays
+            .iter()
+            .map(|relay| vec!["r".to_string(), relay.clone()])
+            .collect();
+        let event = self.sign(EventBuilder::new(Kind::RelayList, "", custom_tags(tags))).await?;
+        
+        // Relay lists are looked up on the configured relays
+        self.send_event(&self.relays, &event).await
     }
     
-    async fn upload_to_blossom(&self, image_bytes: &[u8], blossom_url: &str, _client: &Client) -> Result<String> {
+    /// Strip EXIF data from each image and upload it to the Blossom server.
+    /// Images that fail are left out of the post.
+    async fn upload_images(&self, post: &Post) -> Vec<UploadedImage> {
+        use crate::image_utils;
+        
+        let mut images = Vec::new();
+        if post.images.is_empty() {
+            return images;
+        }
+        let Some(blossom_url) = &self.image_host_url else {
+            eprintln!("[Nostr] No Blossom server configured, skipping image upload");
+            return images;
+        };
+        
+        eprintln!("[Nostr] Uploading {} images to Blossom", post.images.len());
+        for (i, image) in post.images.iter().enumerate() {
+            // Strip EXIF data for privacy (Blossom requirement)
+            let processor = image_utils::ImageProcessor::new()
+                .with_exif_stripping();
+            
+            let processed_bytes = match processor.process(&image.bytes) {
+                Ok(bytes) => {
+                    eprintln!("[Nostr] Image {} processed ({} -> {} bytes)", 
+                        i + 1, image.bytes.len(), bytes.len());
+                    bytes
+                }
+                Err(e) => {
+                    eprintln!("[Nostr] Failed to process image {}: {}", i + 1, e);
+                    continue;
+                }
+            };
+            
+            match self.upload_to_blossom(&processed_bytes, blossom_url).await {
+                Ok(url) => {
+                    eprintln!("[Nostr] Image {} uploaded: {}", i + 1, url);
+                    images.push(UploadedImage::new(url, &image.bytes, &processed_bytes, &image.alt));
+                }
+                Err(e) => {
+                    eprintln!("[Nostr] Failed to upload image {}: {}", i + 1, e);
+                }
+            }
+        }
+        images
+    }
+    
+    async fn upload_to_blossom(&self, image_bytes: &[u8], blossom_url: &str) -> Result<String> {
         use crate::image_utils;
-        use sha2::{Sha256, Digest};
         use base64::Engine;
         
-        // Calculate SHA-256 hash
-        let mut hasher = Sha256::new();
-        hasher.update(image_bytes);
-        let hash = format!("{:x}", hasher.finalize());
+        let hash = sha256_hex(image_bytes);
         
         let mime_type = image_utils::get_mime_type(image_bytes)?;
         
@@ -180,28 +409,28 @@ impl NostrPlatform {
         let timestamp = Timestamp::now();
         
         let auth_tags = vec![
-            Tag::custom(TagKind::Custom(std::borrow::Cow::Borrowed("t")), vec!["upload"]),
-            Tag::custom(TagKind::Custom(std::borrow::Cow::Borrowed("x")), vec![&hash]),
-            Tag::custom(TagKind::Custom(std::borrow::Cow::Borrowed("expiration")), 
-                vec![&(timestamp.as_u64() + 600).to_string()]), // 10 min expiration
+            vec!["t".to_string(), "upload".to_string()],
+            vec!["x".to_string(), hash],
+            vec!["expiration".to_string(), (timestamp.as_u64() + 600).to_string()], // 10 min expiration
         ];
         
-        // Create and sign auth event
-        let keys = self.get_keys().await?;
-        let auth_event = EventBuilder::new(Kind::Custom(24242), "", auth_tags)
-            .sign_with_keys(&keys)?;
+        // Servers that only accept known pubkeys need the user's signature
+        let auth_event = self.sign(EventBuilder::new(Kind::Custom(24242), "", custom_tags(auth_tags))).await?;
         
         let engine = base64::engine::general_purpose::STANDARD;
         let auth_header = engine.encode(auth_event.as_json());
         
-        // Upload to Blossom
+        // Upload to Blossom (blobs are content-addressed, so retrying is safe)
         let http_client = reqwest::Client::new();
-        let response = http_client
-            .put(&upload_url)
-            .header("Authorization", format!("Nostr {}", auth_header))
-            .header("Content-Type", &mime_type)
-            .body(image_bytes.to_vec())
-            .send()
+        let response = self
+            .retry
+            .send("Blossom upload", Idempotency::Idempotent, || {
+                Ok(http_client
+                    .put(&upload_url)
+                    .header("Authorization", format!("Nostr {}", auth_header))
+                    .header("Content-Type", &mime_type)
+                    .body(image_bytes.to_vec()))
+            })
             .await?;
         
         if !response.status().is_success() {
@@ -218,257 +447,306 @@ impl NostrPlatform {
         Ok(blossom_response.url)
     }
     
-    async fn post_with_pleb_signer(&self, post: &Post) -> Result<PostResult> {
-        use crate::image_utils;
-        
-        eprintln!("[Nostr] Posting via Pleb_Signer...");
-        
-        // Get pubkey from Pleb_Signer
-        let _pubkey_hex = match get_pleb_signer_pubkey().await {
-            Ok(pk) => pk,
-            Err(e) => {
-                eprintln!("[Nostr] Failed to get pubkey from Pleb_Signer: {}", e);
-                return Ok(PostResult::Error {
-                    message: format!("Failed to get pubkey: {}", e)
-                });
-            }
-        };
-        
-        // Upload images if any
-        let mut image_urls = Vec::new();
-        if !post.images.is_empty() {
-            if let Some(blossom_url) = &self.image_host_url {
-                eprintln!("[Nostr] Uploading {} images to Blossom", post.images.len());
-                
-                // For PlebSigner, we need a temporary client for Blossom uploads
-                let temp_keys = Keys::generate();
-                let temp_client = Client::new(temp_keys);
-                
-                for (i, image_bytes) in post.images.iter().enumerate() {
-                    // Strip EXIF data for privacy (Blossom requirement)
-                    let processor = image_utils::ImageProcessor::new()
-                        .with_exif_stripping();
-                    
-                    let processed_bytes = match processor.process(image_bytes) {
-                        Ok(bytes) => {
-                            eprintln!("[Nostr] Image {} processed ({} -> {} bytes)", 
-                                i + 1, image_bytes.len(), bytes.len());
-                            bytes
-                        }
-                        Err(e) => {
-                            eprintln!("[Nostr] Failed to process image {}: {}", i + 1, e);
-                            continue;
-                        }
-                    };
-                    
-                    match self.upload_to_blossom(&processed_bytes, blossom_url, &temp_client).await {
-                        Ok(url) => {
-                            eprintln!("[Nostr] Image {} uploaded: {}", i + 1, url);
-                            image_urls.push(url);
-                        }
-                        Err(e) => {
-                            eprintln!("[Nostr] Failed to upload image {}: {}", i + 1, e);
-                        }
-                    }
-                }
-            } else {
-                eprintln!("[Nostr] No Blossom server configured, skipping image upload");
-            }
-        }
-        
-        // Create content with image URLs
-        let mut content = post.text.clone();
-        for url in &image_urls {
-            content.push_str(&format!("\n{}", url));
-        }
-        
-        // Create unsigned event
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        
-        let unsigned_event = serde_json::json!({
-            "kind": 1,
-            "content": content,
-            "tags": [],
-            "created_at": timestamp
-        });
-        
-        eprintln!("[Nostr] Requesting signature from Pleb_Signer...");
-        let signed_event_json = match sign_event_with_pleb_signer(&unsigned_event.to_string()).await {
-            Ok(se) => se,
-            Err(e) => {
-                eprintln!("[Nostr] Failed to sign event with Pleb_Signer: {}", e);
-                return Ok(PostResult::Error {
-                    message: format!("Failed to sign event: {}", e)
-                });
-            }
+    /// Ask relays to delete an event (NIP-09)
+    async fn delete_event(&self, remote_id: &RemoteId) -> Result<PostOutcome, PostError> {
+        let RemoteId::Event(hex) = remote_id else {
+            return Err(PostError::new(ErrorKind::Validation, "Not a Nostr event"));
         };
+        let event_id = EventId::from_hex(hex)
+            .map_err(|e| PostError::new(ErrorKind::Validation, format!("Invalid event id: {}", e)))?;
         
-        eprintln!("[Nostr] Event signed successfully");
-        
-        // Parse signed event
-        let signed_event: nostr_sdk::Event = match serde_json::from_str(&signed_event_json) {
-            Ok(e) => e,
-            Err(e) => {
-                eprintln!("[Nostr] Failed to parse signed event: {}", e);
-                return Ok(PostResult::Error {
-                    message: format!("Failed to parse signed event: {}", e)
-                });
-            }
-        };
+        eprintln!("[Nostr] Requesting deletion of {}", hex);
         
-        // Create client without keys (we'll send pre-signed event)
-        let keys = match self.get_keys().await {
-            Ok(k) => k,
-            Err(e) => {
-                return Ok(PostResult::Error {
-                    message: format!("Failed to create client: {}", e)
-                });
-            }
-        };
-        let client = Client::new(keys);
+        let event = self.sign(EventBuilder::new(Kind::EventDeletion, "", vec![Tag::event(event_id)])).await?;
+        let relays = self.outbox_relays(event.pubkey, &[]).await;
+        let statuses = self.send_event(&relays, &event).await?;
         
-        // Add relays
-        eprintln!("[Nostr] Adding {} relays...", self.relays.len());
-        for relay_url in &self.relays {
-            eprintln!("[Nostr] Adding relay: {}", relay_url);
-            match client.add_relay(relay_url).await {
-                Ok(_) => eprintln!("[Nostr] Relay added: {}", relay_url),
-                Err(e) => eprintln!("[Nostr] Failed to add relay {}: {}", relay_url, e),
-            }
+        Ok(PostOutcome {
+            remote_id: Some(remote_id.clone()),
+            relays: statuses,
+            ..PostOutcome::success(PlatformId::Nostr)
+        })
+    }
+}
+
+/// Publish the relays from `credentials` as the user's relay list (NIP-65)
+pub async fn publish_relay_list(credentials: &Credentials) -> Result<Vec<RelayStatus>, PostError> {
+    let platform = NostrPlatform::from_credentials(credentials)
+        .ok_or_else(|| PostError::new(ErrorKind::NotConfigured, "Nostr isn't configured"))?;
+    platform.publish_relay_list().await
+}
+
+/// New secret key for doh to identify itself to remote signers
+pub fn generate_app_key() -> Result<String> {
+    Ok(Keys::generate().secret_key().to_bech32()?)
+}
+
+/// `nostrconnect://` URI for a remote signer to connect to doh through
+/// `relays` (NIP-46)
+pub fn nostrconnect_uri(app_key: &str, relays: &[String]) -> Result<String> {
+    let app_keys = Keys::parse(app_key)?;
+    let mut uri = reqwest::Url::parse(&format!("nostrconnect://{}", app_keys.public_key().to_hex()))?;
+    {
+        let mut query = uri.query_pairs_mut();
+        for relay in relays {
+            query.append_pair("relay", relay);
         }
-        
-        eprintln!("[Nostr] Connecting to relays...");
-        client.connect().await;
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-        
-        // Send pre-signed event
-        eprintln!("[Nostr] Sending signed event to relays...");
-        match client.send_event(signed_event).await {
-            Ok(event_id) => {
-                eprintln!("[Nostr] Event sent successfully: {:?}", event_id);
-                Ok(PostResult::Success { url: None })
-            }
-            Err(e) => {
-                eprintln!("[Nostr] Failed to send event: {}", e);
-                Ok(PostResult::Error {
-                    message: format!("Failed to send event: {}", e)
-                })
-            }
+        query.append_pair("metadata", &serde_json::json!({ "name": "doh" }).to_string());
+    }
+    Ok(uri.to_string())
+}
+
+/// Wait for a remote signer to connect through a `nostrconnect://` URI.
+/// Returns the `bunker://` URI that reaches the signer from then on.
+pub async fn accept_remote_signer(uri: &str, app_key: &str) -> Result<String> {
+    let signer = NostrConnect::new(NostrConnectURI::parse(uri)?, Keys::parse(app_key)?, CONNECT_TIMEOUT, None)?;
+    Ok(signer.bunker_uri().await?.to_string())
+}
+
+fn event_hex(remote_id: &RemoteId) -> Result<String, PostError> {
+    match remote_id {
+        RemoteId::Event(hex) => Ok(hex.clone()),
+        _ => Err(PostError::new(ErrorKind::Validation, "Not a Nostr event")),
+    }
+}
+
+/// NIP-10 `e` tags marking a reply's thread root and direct parent, and the
+/// NIP-18 `q` tag of a quoted event
+fn reference_tags(post: &Post) -> Result<Vec<Vec<String>>, PostError> {
+    let mut tags = Vec::new();
+    if let Some(reply) = &post.reply {
+        let root = event_hex(&reply.root)?;
+        let parent = event_hex(&reply.parent)?;
+        
+        tags.push(vec!["e".to_string(), root.clone(), String::new(), "root".to_string()]);
+        // Direct replies to the root only carry the root marker
+        if parent != root {
+            tags.push(vec!["e".to_string(), parent, String::new(), "reply".to_string()]);
         }
     }
+    if let Some(quote) = &post.quote {
+        tags.push(vec!["q".to_string(), event_hex(quote)?]);
+    }
+    Ok(tags)
 }
 
-impl Platform for NostrPlatform {
-    fn name(&self) -> &'static str {
-        "Nostr"
+impl UploadedImage {
+    fn new(url: String, original: &[u8], uploaded: &[u8], alt: &str) -> Self {
+        use crate::image_utils;
+        
+        Self {
+            url,
+            mime_type: image_utils::get_mime_type(uploaded)
+                .unwrap_or_else(|_| "application/octet-stream".to_string()),
+            sha256: sha256_hex(uploaded),
+            original_sha256: sha256_hex(original),
+            size: uploaded.len(),
+            dimensions: image_utils::dimensions(uploaded).ok(),
+            blurhash: image_utils::blurhash(uploaded)
+                .map_err(|e| eprintln!("[Nostr] Failed to compute blurhash: {}", e))
+                .ok(),
+            alt: alt.to_string(),
+        }
     }
     
-    async fn is_authenticated(&self) -> bool {
-        match &self.auth {
-            NostrAuth::Nsec(key) => !key.is_empty() && Keys::parse(key).is_ok(),
-            NostrAuth::PlebSigner => {
-                check_pleb_signer_available().await
-            }
+    /// NIP-94 fields as `(name, value)` pairs, shared by both tag formats
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("url", self.url.clone()),
+            ("m", self.mime_type.clone()),
+            ("x", self.sha256.clone()),
+        ];
+        if let Some((width, height)) = self.dimensions {
+            fields.push(("dim", format!("{}x{}", width, height)));
         }
+        if let Some(blurhash) = &self.blurhash {
+            fields.push(("blurhash", blurhash.clone()));
+        }
+        if !self.alt.is_empty() {
+            fields.push(("alt", self.alt.clone()));
+        }
+        fields
     }
     
-    async fn post(&self, post: &Post) -> Result<PostResult> {
-        eprintln!("[Nostr] Starting post attempt...");
-        
-        match &self.auth {
-            NostrAuth::Nsec(_) => {
-                // Direct posting with nsec key
-                self.post_with_nsec(post).await
-            }
-            NostrAuth::PlebSigner => {
-                // Posting via Pleb_Signer
-                self.post_with_pleb_signer(post).await
-            }
-        }
+    /// Inline media metadata for the note (NIP-92)
+    fn imeta_tag(&self) -> Vec<String> {
+        std::iter::once("imeta".to_string())
+            .chain(self.fields().into_iter().map(|(name, value)| format!("{} {}", name, value)))
+            .collect()
+    }
+    
+    /// Tags of a file metadata event (NIP-94)
+    fn file_metadata_tags(&self) -> Vec<Vec<String>> {
+        let mut tags: Vec<Vec<String>> = self
+            .fields()
+            .into_iter()
+            .map(|(name, value)| vec![name.to_string(), value])
+            .collect();
+        tags.push(vec!["ox".to_string(), self.original_sha256.clone()]);
+        tags.push(vec!["size".to_string(), self.size.to_string()]);
+        tags
     }
 }
 
-#[derive(Deserialize)]
-struct PlebSignerResponse {
-    success: bool,
-    result: Option<String>,
-    error: Option<String>,
+fn sha256_hex(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(bytes))
 }
 
-async fn check_pleb_signer_available() -> bool {
-    // Check if Pleb_Signer is running via D-Bus
-    match zbus::Connection::session().await {
-        Ok(conn) => {
-            let proxy = zbus::fdo::DBusProxy::new(&conn).await;
-            if let Ok(proxy) = proxy {
-                let name = zbus::names::BusName::from_static_str("com.plebsigner.Signer").unwrap();
-                proxy.name_has_owner(name).await.unwrap_or(false)
-            } else {
-                false
-            }
+/// Turn `[name, values...]` lists into tags
+fn custom_tags(tags: Vec<Vec<String>>) -> Vec<Tag> {
+    tags.into_iter()
+        .map(|mut tag| {
+            let values = tag.split_off(1);
+            Tag::custom(TagKind::Custom(std::borrow::Cow::Owned(tag.remove(0))), values)
+        })
+        .collect()
+}
+
+/// Users mentioned as `nostr:npub...` or `nostr:nprofile...` (NIP-27)
+fn mentioned_pubkeys(text: &str) -> Vec<PublicKey> {
+    let mut pubkeys = Vec::new();
+    for word in text.split_whitespace() {
+        let Some(start) = word.find("nostr:") else { continue };
+        let entity = word[start + "nostr:".len()..].trim_end_matches(|c: char| !c.is_ascii_alphanumeric());
+        let pubkey = if entity.starts_with("npub1") {
+            PublicKey::from_bech32(entity).ok()
+        } else if entity.starts_with("nprofile1") {
+            Nip19Profile::from_bech32(entity).ok().map(|profile| profile.public_key)
+        } else {
+            None
+        };
+        if let Some(pubkey) = pubkey.filter(|pubkey| !pubkeys.contains(pubkey)) {
+            pubkeys.push(pubkey);
         }
-        Err(_) => false,
     }
+    pubkeys
 }
 
-async fn get_pleb_signer_pubkey() -> Result<String> {
-    let conn = zbus::Connection::session().await?;
-    
-    let msg = conn.call_method(
-        Some("com.plebsigner.Signer"),
-        "/com/plebsigner/Signer",
-        Some("com.plebsigner.Signer1"),
-        "GetPublicKey",
-        &(),
-    ).await.map_err(|e| anyhow!("D-Bus call failed: {}", e))?;
-    
-    let response: String = msg.body().deserialize()?;
-    
-    eprintln!("[Nostr/PlebSigner] GetPublicKey response: {}", response);
+/// Mention a quoted event in the content so clients render it inline
+fn append_quote(content: &mut String, post: &Post) -> Result<(), PostError> {
+    let Some(quote) = &post.quote else {
+        return Ok(());
+    };
+    let invalid = |e: &dyn Display| PostError::new(ErrorKind::Validation, format!("Invalid quoted event: {}", e));
+    let event_id = EventId::from_hex(&event_hex(quote)?).map_err(|e| invalid(&e))?;
+    let note = event_id.to_bech32().map_err(|e| invalid(&e))?;
+    content.push_str(&format!("\n\nnostr:{}", note));
+    Ok(())
+}
+
+/// Event referenced by a `note`/`nevent` link
+fn resolve_event(url: &str) -> Result<ReplyRef, PostError> {
+    let Some(ParsedUrl::Nostr { bech32 }) = reference::parse(url) else {
+        return Err(PostError::new(ErrorKind::Validation, format!("Not a Nostr event: {}", url)));
+    };
+    let event_id = match Nip19::from_bech32(&bech32) {
+        Ok(Nip19::EventId(event_id)) => event_id,
+        Ok(Nip19::Event(event)) => event.event_id,
+        Ok(_) => return Err(PostError::new(ErrorKind::Validation, format!("Not an event: {}", bech32))),
+        Err(e) => return Err(PostError::new(ErrorKind::Validation, format!("Invalid {}: {}", bech32, e))),
+    };
     
-    let parsed: PlebSignerResponse = serde_json::from_str(&response)?;
+    // Without fetching the event we can't see its own thread, so treat it
+    // as the root
+    let id = RemoteId::Event(event_id.to_hex());
+    Ok(ReplyRef {
+        root: id.clone(),
+        parent: id,
+    })
+}
+
+/// Build an outcome from the relays that accepted or rejected an event
+fn event_outcome(
+    event_id: &EventId,
+    author: PublicKey,
+    viewer_url: &str,
+    relays: Vec<RelayStatus>,
+) -> PostOutcome {
+    let hints = relays
+        .iter()
+        .filter(|relay| relay.accepted)
+        .take(MAX_RELAY_HINTS)
+        .map(|relay| relay.url.clone());
     
-    if !parsed.success {
-        return Err(anyhow!("Pleb_Signer error: {}", parsed.error.unwrap_or_else(|| "Unknown error".to_string())));
+    PostOutcome {
+        url: event_link(event_id, author, hints).map(|id| format!("{}/{}", viewer_url, id)),
+        remote_id: Some(RemoteId::Event(event_id.to_hex())),
+        relays,
+        ..PostOutcome::success(PlatformId::Nostr)
     }
-    
-    let result_json = parsed.result.ok_or_else(|| anyhow!("No result in response"))?;
-    
-    // Result is double-encoded JSON string
-    let pubkey: String = serde_json::from_str(&result_json)?;
-    
-    Ok(pubkey)
 }
 
-async fn sign_event_with_pleb_signer(event_json: &str) -> Result<String> {
-    let conn = zbus::Connection::session().await?;
-    
-    let app_id = "com.sgtapple.doh";
-    let msg = conn.call_method(
-        Some("com.plebsigner.Signer"),
-        "/com/plebsigner/Signer",
-        Some("com.plebsigner.Signer1"),
-        "SignEvent",
-        &(event_json, app_id),
-    ).await.map_err(|e| anyhow!("D-Bus call failed: {}", e))?;
-    
-    let response: String = msg.body().deserialize()?;
-    
-    eprintln!("[Nostr/PlebSigner] SignEvent response: {}", response);
+/// Per-relay results, sorted by relay URL. Fails with each relay's reason if
+/// no relay accepted the event.
+fn check_relays(mut relays: Vec<RelayStatus>) -> Result<Vec<RelayStatus>, PostError> {
+    relays.sort_by(|a, b| a.url.cmp(&b.url));
+    if relays.iter().any(|relay| relay.accepted) {
+        return Ok(relays);
+    }
     
-    let parsed: PlebSignerResponse = serde_json::from_str(&response)?;
+    let reasons: Vec<&str> = relays.iter().filter_map(|relay| relay.message.as_deref()).collect();
+    // Retrying is only worth it if some relay couldn't be reached
+    let kind = if reasons.iter().any(|reason| publisher::error_kind(reason) == ErrorKind::Network) {
+        ErrorKind::Network
+    } else {
+        reasons.first().map_or(ErrorKind::Network, |reason| publisher::error_kind(reason))
+    };
+    let details: Vec<String> = relays
+        .iter()
+        .map(|relay| format!("{}: {}", relay.url, relay.message.as_deref().unwrap_or("rejected")))
+        .collect();
+    let retry_safe = reasons.len() == relays.len() && reasons.iter().all(|reason| publisher::never_stored(reason));
+    Err(PostError::new(kind, format!("No relay accepted the event ({})", details.join("; "))).with_retry_safe(retry_safe))
+}
+
+/// NIP-19 identifier for an event: an `nevent` carrying the author and the
+/// relays that have it, or a bare `note` if that can't be encoded
+fn event_link(
+    event_id: &EventId,
+    author: PublicKey,
+    relays: impl IntoIterator<Item = String>,
+) -> Option<String> {
+    Nip19Event::new(*event_id, relays)
+        .author(author)
+        .to_bech32()
+        .or_else(|_| event_id.to_bech32())
+        .map_err(|e| eprintln!("[Nostr] Failed to encode event id: {}", e))
+        .ok()
+}
+
+impl Platform for NostrPlatform {
+    fn id(&self) -> PlatformId {
+        PlatformId::Nostr
+    }
     
-    if !parsed.success {
-        return Err(anyhow!("Pleb_Signer error: {}", parsed.error.unwrap_or_else(|| "Unknown error".to_string())));
+    fn is_authenticated(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            match &self.auth {
+                NostrAuth::Nsec(key) => !key.is_empty() && Keys::parse(key).is_ok(),
+                NostrAuth::PlebSigner => {
+                    PlebSigner::is_available().await
+                }
+                NostrAuth::Bunker { uri, app_key } => {
+                    NostrConnectURI::parse(uri).is_ok() && Keys::parse(app_key).is_ok()
+                }
+            }
+        })
     }
     
-    let result_json = parsed.result.ok_or_else(|| anyhow!("No result in response"))?;
+    fn post<'a>(&'a self, post: &'a Post) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+        Box::pin(async move {
+            eprintln!("[Nostr] Starting post attempt...");
+            
+            self.publish(post).await
+        })
+    }
     
-    // Result is double-encoded JSON string containing the signed event
-    let signed_event_json: String = serde_json::from_str(&result_json)?;
+    fn delete<'a>(&'a self, remote_id: &'a RemoteId) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+        Box::pin(self.delete_event(remote_id))
+    }
     
-    Ok(signed_event_json)
+    fn resolve<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<ReplyRef, PostError>> {
+        Box::pin(async move { resolve_event(url) })
+    }
 }
@@ -0,0 +1,264 @@
+// SPDX-License-Identifier: MIT
+
+//! Proof of work (NIP-13): a `nonce` tag is tried until the event id starts
+//! with enough zero bits
+
+use crate::platforms::{ErrorKind, PostError};
+use nostr_sdk::prelude::{EventBuilder, UnsignedEvent};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, AtomicUsize, Ordering};
+
+/// Stands in for the nonce while the rest of the event is serialized once
+const NONCE_PLACEHOLDER: &str = "doh-pow-nonce";
+
+/// Attempts between progress updates and checks for cancellation
+const PROGRESS_INTERVAL: u64 = 10_000;
+
+/// Shared view of the mining runs, so the UI can show progress and stop them.
+/// One handle is shared by every post, including background retries.
+#[derive(Debug, Clone, Default)]
+pub struct PowProgress {
+    state: Arc<PowState>,
+}
+
+#[derive(Debug, Default)]
+struct PowState {
+    /// Target of the latest run in progress, 0 when not mining
+    difficulty: AtomicU8,
+    attempts: AtomicU64,
+    cancelled: AtomicBool,
+    /// Runs in progress
+    active: AtomicUsize,
+}
+
+impl PowProgress {
+    /// Difficulty and attempts so far, while mining
+    pub fn mining(&self) -> Option<(u8, u64)> {
+        let difficulty = self.state.difficulty.load(Ordering::Relaxed);
+        (difficulty > 0).then(|| (difficulty, self.state.attempts.load(Ordering::Relaxed)))
+    }
+
+    /// Stop every run in progress. Their posts fail instead of being
+    /// published without work.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Ends a mining run, and stops its mining thread if the post is dropped
+/// before it finishes, e.g. when it times out
+struct MiningRun {
+    progress: PowProgress,
+    stop: Arc<AtomicBool>,
+    finished: bool,
+}
+
+impl Drop for MiningRun {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        // The last run to end clears the shared state for the next post
+        if self.progress.state.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.progress.state.difficulty.store(0, Ordering::Relaxed);
+            self.progress.state.cancelled.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Add a `nonce` tag to `unsigned` so its id has at least `difficulty`
+/// leading zero bits. Mining runs on the blocking thread pool.
+pub async fn mine(unsigned: UnsignedEvent, difficulty: u8, progress: &PowProgress) -> Result<UnsignedEvent, PostError> {
+    eprintln!("[Nostr] Mining proof of work (difficulty {})...", difficulty);
+    let (prefix, suffix) = serialize_around_nonce(&unsigned, difficulty)?;
+    progress.state.active.fetch_add(1, Ordering::SeqCst);
+    progress.state.attempts.store(0, Ordering::Relaxed);
+    progress.state.difficulty.store(difficulty, Ordering::Relaxed);
+
+    let mut run = MiningRun {
+        progress: progress.clone(),
+        stop: Arc::new(AtomicBool::new(false)),
+        finished: false,
+    };
+    let miner = progress.clone();
+    let stop = run.stop.clone();
+    let nonce = tokio::task::spawn_blocking(move || find_nonce(&prefix, &suffix, difficulty, &miner, &stop)).await;
+    run.finished = true;
+    drop(run);
+
+    let nonce = nonce.map_err(|e| PostError::new(ErrorKind::Other, format!("Proof of work failed: {}", e)))?;
+    let Some(nonce) = nonce else {
+        eprintln!("[Nostr] Proof of work cancelled");
+        return Err(PostError::new(ErrorKind::Cancelled, "Proof of work cancelled"));
+    };
+    eprintln!("[Nostr] Found nonce {}", nonce);
+
+    let mut tags: Vec<_> = unsigned.tags.iter().filter(|tag| !is_nonce(tag.as_slice())).cloned().collect();
+    tags.extend(super::custom_tags(vec![vec![
+        "nonce".to_string(),
+        nonce.to_string(),
+        difficulty.to_string(),
+    ]]));
+    Ok(EventBuilder::new(unsigned.kind, &unsigned.content, tags)
+        .custom_created_at(unsigned.created_at)
+        .build(unsigned.pubkey))
+}
+
+/// Number of leading zero bits in an event id
+pub fn leading_zero_bits(id: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in id {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// The event's id serialization (NIP-01) with a nonce tag appended, split
+/// where the nonce goes
+fn serialize_around_nonce(unsigned: &UnsignedEvent, difficulty: u8) -> Result<(String, String), PostError> {
+    let mut tags: Vec<Vec<String>> = unsigned
+        .tags
+        .iter()
+        .map(|tag| tag.as_slice().to_vec())
+        .filter(|tag| !is_nonce(tag))
+        .collect();
+    tags.push(vec!["nonce".to_string(), NONCE_PLACEHOLDER.to_string(), difficulty.to_string()]);
+
+    let serialized = serde_json::json!([
+        0,
+        unsigned.pubkey.to_hex(),
+        unsigned.created_at.as_u64(),
+        unsigned.kind.as_u16(),
+        tags,
+        unsigned.content,
+    ])
+    .to_string();
+
+    // Tags come before the content, so the first match is ours
+    let placeholder = format!("\"{}\"", NONCE_PLACEHOLDER);
+    let start = serialized
+        .find(&placeholder)
+        .ok_or_else(|| PostError::new(ErrorKind::Other, "Failed to prepare proof of work"))?;
+    let prefix = serialized[..=start].to_string();
+    let suffix = serialized[start + placeholder.len() - 1..].to_string();
+    Ok((prefix, suffix))
+}
+
+/// Try nonces until one meets `difficulty`, or `None` once cancelled or
+/// stopped
+fn find_nonce(prefix: &str, suffix: &str, difficulty: u8, progress: &PowProgress, stop: &AtomicBool) -> Option<u64> {
+    let base = Sha256::new_with_prefix(prefix.as_bytes());
+    for nonce in 0u64.. {
+        if nonce.is_multiple_of(PROGRESS_INTERVAL) {
+            progress.state.attempts.store(nonce, Ordering::Relaxed);
+            if progress.is_cancelled() || stop.load(Ordering::Relaxed) {
+                return None;
+            }
+        }
+
+        let mut hasher = base.clone();
+        hasher.update(nonce.to_string().as_bytes());
+        hasher.update(suffix.as_bytes());
+        if leading_zero_bits(&hasher.finalize()) >= u32::from(difficulty) {
+            return Some(nonce);
+        }
+    }
+    None
+}
+
+fn is_nonce(tag: &[String]) -> bool {
+    tag.first().is_some_and(|name| name == "nonce")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::prelude::Keys;
+
+    fn note(keys: &Keys, tags: Vec<Vec<String>>) -> UnsignedEvent {
+        EventBuilder::text_note("Hello, proof of work", super::super::custom_tags(tags)).build(keys.public_key())
+    }
+
+    fn nonce_tags(event: &nostr_sdk::prelude::Event) -> Vec<Vec<String>> {
+        event
+            .tags
+            .iter()
+            .map(|tag| tag.as_slice().to_vec())
+            .filter(|tag| is_nonce(tag))
+            .collect()
+    }
+
+    #[test]
+    fn counts_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x80]), 8);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00, 0x0f]), 20);
+        assert_eq!(leading_zero_bits(&[0x00, 0x01, 0x00]), 15);
+        assert_eq!(leading_zero_bits(&[0x00; 32]), 256);
+    }
+
+    #[tokio::test]
+    async fn mined_id_meets_difficulty() {
+        let keys = Keys::generate();
+        let progress = PowProgress::default();
+        let mined = mine(note(&keys, Vec::new()), 12, &progress).await.unwrap();
+        let event = mined.sign_with_keys(&keys).unwrap();
+
+        assert!(leading_zero_bits(event.id.as_bytes()) >= 12);
+        assert_eq!(nonce_tags(&event)[0][2], "12");
+        assert_eq!(event.content, "Hello, proof of work");
+        assert_eq!(progress.mining(), None);
+    }
+
+    #[tokio::test]
+    async fn replaces_an_existing_nonce() {
+        let keys = Keys::generate();
+        let tags = vec![
+            vec!["t".to_string(), "doh".to_string()],
+            vec!["nonce".to_string(), "1".to_string(), "4".to_string()],
+        ];
+        let mined = mine(note(&keys, tags), 4, &PowProgress::default()).await.unwrap();
+        let event = mined.sign_with_keys(&keys).unwrap();
+
+        assert_eq!(nonce_tags(&event).len(), 1);
+        assert_eq!(event.tags.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn cancelled_run_fails() {
+        let keys = Keys::generate();
+        let progress = PowProgress::default();
+        progress.cancel();
+        let error = mine(note(&keys, Vec::new()), 255, &progress).await.unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::Cancelled);
+        assert_eq!(progress.mining(), None);
+    }
+
+    #[tokio::test]
+    async fn cancel_reaches_every_run() {
+        let keys = Keys::generate();
+        let progress = PowProgress::default();
+        let first = mine(note(&keys, Vec::new()), 255, &progress);
+        let second = mine(note(&keys, Vec::new()), 255, &progress);
+        let cancel = async {
+            while progress.state.active.load(Ordering::SeqCst) < 2 {
+                tokio::task::yield_now().await;
+            }
+            progress.cancel();
+        };
+        let (first, second, ()) = tokio::join!(first, second, cancel);
+
+        assert_eq!(first.unwrap_err().kind, ErrorKind::Cancelled);
+        assert_eq!(second.unwrap_err().kind, ErrorKind::Cancelled);
+        assert_eq!(progress.mining(), None);
+    }
+}
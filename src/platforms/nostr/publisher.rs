@@ -0,0 +1,167 @@
+// SPDX-License-Identifier: MIT
+
+//! Sends events over one connection per relay, so each relay's `OK` answer
+//! (NIP-01) is known, and authenticates to relays that require it (NIP-42)
+
+use super::signer::NostrSigner;
+use crate::platforms::{ErrorKind, RelayStatus};
+use futures_util::{SinkExt, StreamExt};
+use nostr_sdk::prelude::{ClientMessage, Event, EventBuilder, EventId, JsonUtil, RelayMessage, Url};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+/// How long a relay gets to accept the connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long we wait for each message from a relay. Signing an AUTH
+/// challenge doesn't count, since the user may have to approve it.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Send `event` to every relay at once and report what each one said
+pub async fn send(signer: &dyn NostrSigner, relays: &[String], event: &Event) -> Vec<RelayStatus> {
+    let sends = relays.iter().map(|relay_url| async move {
+        let result = send_to(signer, relay_url, event).await;
+        match &result {
+            Ok(_) => eprintln!("[Nostr] {} accepted {}", relay_url, event.id),
+            Err(e) => eprintln!("[Nostr] {} rejected {}: {}", relay_url, event.id, e),
+        }
+        let (accepted, message) = match result {
+            Ok(message) => (true, message),
+            Err(message) => (false, Some(message)),
+        };
+        RelayStatus {
+            url: relay_url.clone(),
+            accepted,
+            message,
+        }
+    });
+    futures_util::future::join_all(sends).await
+}
+
+/// Machine-readable prefixes relays put on rejections (NIP-01)
+const REJECTION_PREFIXES: &[&str] = &[
+    "duplicate", "pow", "blocked", "rate-limited", "invalid", "restricted", "mute", "error", "auth-required",
+];
+
+/// Whether a relay's failure means it can't have stored the event: the
+/// connection never opened, or the relay answered with a rejection. Anything
+/// else, like a dropped connection, leaves it unknown.
+pub fn never_stored(message: &str) -> bool {
+    let prefix = message.split(':').next().unwrap_or_default();
+    REJECTION_PREFIXES.contains(&prefix)
+        || message.starts_with("Couldn't connect")
+        || message.starts_with("Invalid relay URL")
+        || message.starts_with("Authentication failed")
+}
+
+/// What kind of failure a relay's rejection is, going by its NIP-01
+/// machine-readable prefix. Anything else counts as a connection problem.
+pub fn error_kind(message: &str) -> ErrorKind {
+    let prefix = message.split(':').next().unwrap_or_default();
+    match prefix {
+        "auth-required" | "restricted" => ErrorKind::Auth,
+        "rate-limited" => ErrorKind::RateLimit,
+        "pow" | "invalid" | "blocked" => ErrorKind::Validation,
+        _ => ErrorKind::Network,
+    }
+}
+
+/// Publish to a single relay. Returns the relay's message on acceptance,
+/// or why it didn't accept the event.
+async fn send_to(signer: &dyn NostrSigner, relay_url: &str, event: &Event) -> Result<Option<String>, String> {
+    let url = Url::parse(relay_url).map_err(|e| format!("Invalid relay URL: {}", e))?;
+    let (mut socket, _) = tokio::time::timeout(CONNECT_TIMEOUT, tokio_tungstenite::connect_async(url.as_str()))
+        .await
+        .map_err(|_| "Couldn't connect: timed out".to_string())?
+        .map_err(|e| format!("Couldn't connect: {}", e))?;
+
+    let event_message = ClientMessage::event(event.clone()).as_json();
+    socket
+        .send(Message::Text(event_message.clone()))
+        .await
+        .map_err(|e| format!("Failed to send event: {}", e))?;
+
+    let mut challenge: Option<String> = None;
+    // The AUTH event we sent, once we had to authenticate
+    let mut auth_id: Option<EventId> = None;
+    // Why the relay turned the event down until we authenticate
+    let mut auth_required: Option<String> = None;
+    let mut notice: Option<String> = None;
+
+    loop {
+        let text = match tokio::time::timeout(ANSWER_TIMEOUT, socket.next()).await {
+            Err(_) => {
+                let reason = auth_required.or(notice);
+                return Err(reason.unwrap_or_else(|| "No answer from relay".to_string()));
+            }
+            Ok(None) | Ok(Some(Ok(Message::Close(_)))) => {
+                let reason = auth_required.or(notice);
+                return Err(reason.unwrap_or_else(|| "Connection closed".to_string()));
+            }
+            Ok(Some(Err(e))) => return Err(format!("Connection failed: {}", e)),
+            Ok(Some(Ok(Message::Text(text)))) => text,
+            // Pings are answered by the socket itself
+            Ok(Some(Ok(_))) => continue,
+        };
+
+        match RelayMessage::from_json(&text) {
+            Ok(RelayMessage::Ok { event_id, status, message }) if event_id == event.id => {
+                if status {
+                    let _ = socket.close(None).await;
+                    return Ok(Some(message).filter(|message| !message.is_empty()));
+                }
+                if message.starts_with("auth-required:") && auth_id.is_none() {
+                    auth_required = Some(message);
+                } else {
+                    let _ = socket.close(None).await;
+                    return Err(message);
+                }
+            }
+            Ok(RelayMessage::Ok { event_id, status, message }) if Some(event_id) == auth_id => {
+                if !status {
+                    let _ = socket.close(None).await;
+                    return Err(format!("Authentication failed: {}", message));
+                }
+                eprintln!("[Nostr] Authenticated to {}", relay_url);
+                // From here on the relay may store the event
+                auth_required = None;
+                socket
+                    .send(Message::Text(event_message.clone()))
+                    .await
+                    .map_err(|e| format!("Failed to send event: {}", e))?;
+            }
+            Ok(RelayMessage::Auth { challenge: new_challenge }) => challenge = Some(new_challenge),
+            Ok(RelayMessage::Notice { message }) => {
+                eprintln!("[Nostr] Notice from {}: {}", relay_url, message);
+                notice = Some(message);
+            }
+            _ => {}
+        }
+
+        // Only sign a challenge once the relay actually wants it, so the
+        // user isn't asked to approve logins to relays that don't need one
+        if auth_required.is_none() || auth_id.is_some() {
+            continue;
+        }
+        let Some(challenge) = challenge.as_deref() else {
+            continue;
+        };
+        eprintln!("[Nostr] Authenticating to {}", relay_url);
+        let auth = sign_auth(signer, challenge, url.clone()).await?;
+        auth_id = Some(auth.id);
+        socket
+            .send(Message::Text(ClientMessage::auth(auth).as_json()))
+            .await
+            .map_err(|e| format!("Failed to authenticate: {}", e))?;
+    }
+}
+
+/// Sign the relay's AUTH challenge (kind 22242) as the user
+async fn sign_auth(signer: &dyn NostrSigner, challenge: &str, relay_url: Url) -> Result<Event, String> {
+    let public_key = signer.get_public_key().await.map_err(|e| e.to_string())?;
+    let unsigned = EventBuilder::auth(challenge, relay_url).build(public_key);
+    signer
+        .sign_event(unsigned)
+        .await
+        .map_err(|e| format!("Authentication failed: {}", e))
+}
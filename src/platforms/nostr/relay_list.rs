@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: MIT
+
+//! Outbox model (NIP-65): notes go to the author's write relays and to the
+//! read relays of everyone they mention
+
+use nostr_sdk::prelude::{Client, Event, Filter, Kind, PublicKey};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How long relays get to return relay lists
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Read relays used per mentioned user, so a long list doesn't fan out
+const MAX_READ_RELAYS_PER_MENTION: usize = 2;
+
+/// Relays from a kind-10002 event
+#[derive(Debug, Default, Clone)]
+pub struct RelayList {
+    pub read: Vec<String>,
+    pub write: Vec<String>,
+}
+
+impl RelayList {
+    /// `r` tags without a marker are for both reading and writing
+    pub fn from_event(event: &Event) -> Self {
+        let mut list = Self::default();
+        for tag in event.tags.iter() {
+            let (name, url, marker) = match tag.as_slice() {
+                [name, url] => (name, url, None),
+                [name, url, marker, ..] => (name, url, Some(marker.as_str())),
+                _ => continue,
+            };
+            if name != "r" {
+                continue;
+            }
+            let url = normalize(url);
+            if marker != Some("write") {
+                list.read.push(url.clone());
+            }
+            if marker != Some("read") {
+                list.write.push(url);
+            }
+        }
+        list
+    }
+}
+
+/// Newest relay list of each of `authors`, as far as the client's relays
+/// know them
+pub async fn fetch(client: &Client, authors: Vec<PublicKey>) -> HashMap<PublicKey, RelayList> {
+    let filter = Filter::new().kind(Kind::RelayList).authors(authors);
+    let events = match client.fetch_events(vec![filter], Some(FETCH_TIMEOUT)).await {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("[Nostr] Failed to fetch relay lists: {}", e);
+            return HashMap::new();
+        }
+    };
+
+    // Relays may still hold replaced lists
+    let mut newest: HashMap<PublicKey, Event> = HashMap::new();
+    for event in events.into_iter() {
+        let replaces = newest
+            .get(&event.pubkey)
+            .is_none_or(|current| event.created_at > current.created_at);
+        if replaces {
+            newest.insert(event.pubkey, event);
+        }
+    }
+    newest
+        .into_iter()
+        .map(|(author, event)| (author, RelayList::from_event(&event)))
+        .collect()
+}
+
+/// Relays to publish `author`'s note to: their write relays, or `fallback`
+/// without a relay list, plus a few read relays of each mentioned user
+pub fn targets(
+    author: &PublicKey,
+    mentions: &[PublicKey],
+    lists: &HashMap<PublicKey, RelayList>,
+    fallback: &[String],
+) -> Vec<String> {
+    let mut relays: Vec<String> = match lists.get(author) {
+        Some(list) if !list.write.is_empty() => list.write.clone(),
+        _ => fallback.iter().map(|relay| normalize(relay)).collect(),
+    };
+    for mention in mentions {
+        let Some(list) = lists.get(mention) else { continue };
+        relays.extend(list.read.iter().take(MAX_READ_RELAYS_PER_MENTION).cloned());
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    relays.retain(|relay| seen.insert(relay.clone()));
+    relays
+}
+
+/// `wss://relay.example/` and `wss://relay.example` are the same relay
+fn normalize(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_string()
+}
@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: MIT
+
+//! Backends that sign events as the user: an nsec key held by doh,
+//! Pleb_Signer over D-Bus, or a NIP-46 remote signer
+
+use crate::platforms::{ErrorKind, PostError};
+use anyhow::anyhow;
+use futures_util::future::BoxFuture;
+use nostr_connect::prelude::NostrConnect;
+use nostr_sdk::prelude::NostrSigner as SdkSigner;
+use nostr_sdk::prelude::{Event, JsonUtil, Keys, NostrConnectURI, PublicKey, UnsignedEvent, nip44};
+use serde::Deserialize;
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Name doh asks Pleb_Signer for approval under
+const APP_ID: &str = "com.sgtapple.doh";
+
+/// Signs events and encrypts messages as the user
+pub trait NostrSigner: Send + Sync {
+    fn get_public_key(&self) -> BoxFuture<'_, Result<PublicKey, PostError>>;
+
+    fn sign_event(&self, unsigned: UnsignedEvent) -> BoxFuture<'_, Result<Event, PostError>>;
+
+    /// Encrypt `content` for `public_key` (NIP-44)
+    fn nip44_encrypt<'a>(&'a self, public_key: &'a PublicKey, content: &'a str) -> BoxFuture<'a, Result<String, PostError>>;
+
+    /// Decrypt a NIP-44 `payload` from `public_key`
+    fn nip44_decrypt<'a>(&'a self, public_key: &'a PublicKey, payload: &'a str) -> BoxFuture<'a, Result<String, PostError>>;
+}
+
+fn signer_error(action: &str, e: impl Display) -> PostError {
+    eprintln!("[Nostr] Failed to {}: {}", action, e);
+    PostError::new(ErrorKind::Auth, format!("Failed to {}: {}", action, e))
+}
+
+/// The user's nsec key
+pub struct LocalSigner {
+    keys: Keys,
+}
+
+impl LocalSigner {
+    pub fn parse(nsec: &str) -> Result<Self, PostError> {
+        eprintln!("[Nostr] Parsing nsec key (length: {})", nsec.len());
+        if nsec.is_empty() {
+            eprintln!("[Nostr] Error: nsec key is empty!");
+            return Err(PostError::new(ErrorKind::Auth, "nsec key is empty"));
+        }
+
+        match Keys::parse(nsec) {
+            Ok(keys) => {
+                eprintln!("[Nostr] Successfully parsed nsec key");
+                Ok(Self { keys })
+            }
+            Err(e) => {
+                eprintln!("[Nostr] Failed to parse nsec key: {}", e);
+                eprintln!("[Nostr] Hint: nsec should start with 'nsec1'");
+                Err(PostError::new(
+                    ErrorKind::Auth,
+                    format!("Invalid nsec key: {}. Must start with 'nsec1'", e),
+                ))
+            }
+        }
+    }
+}
+
+impl NostrSigner for LocalSigner {
+    fn get_public_key(&self) -> BoxFuture<'_, Result<PublicKey, PostError>> {
+        Box::pin(async move { Ok(self.keys.public_key()) })
+    }
+
+    fn sign_event(&self, unsigned: UnsignedEvent) -> BoxFuture<'_, Result<Event, PostError>> {
+        Box::pin(async move {
+            unsigned
+                .sign_with_keys(&self.keys)
+                .map_err(|e| signer_error("sign event", e))
+        })
+    }
+
+    fn nip44_encrypt<'a>(&'a self, public_key: &'a PublicKey, content: &'a str) -> BoxFuture<'a, Result<String, PostError>> {
+        Box::pin(async move {
+            nip44::encrypt(self.keys.secret_key(), public_key, content, nip44::Version::V2)
+                .map_err(|e| signer_error("encrypt", e))
+        })
+    }
+
+    fn nip44_decrypt<'a>(&'a self, public_key: &'a PublicKey, payload: &'a str) -> BoxFuture<'a, Result<String, PostError>> {
+        Box::pin(async move {
+            nip44::decrypt(self.keys.secret_key(), public_key, payload)
+                .map_err(|e| signer_error("decrypt", e))
+        })
+    }
+}
+
+/// Pleb_Signer's D-Bus service, which holds the key and asks the user to
+/// approve each request
+pub struct PlebSigner;
+
+#[derive(Deserialize)]
+struct PlebSignerResponse {
+    success: bool,
+    result: Option<String>,
+    error: Option<String>,
+}
+
+impl PlebSigner {
+    /// Whether Pleb_Signer is running
+    pub async fn is_available() -> bool {
+        match zbus::Connection::session().await {
+            Ok(conn) => {
+                let proxy = zbus::fdo::DBusProxy::new(&conn).await;
+                if let Ok(proxy) = proxy {
+                    let name = zbus::names::BusName::from_static_str("com.plebsigner.Signer").unwrap();
+                    proxy.name_has_owner(name).await.unwrap_or(false)
+                } else {
+                    false
+                }
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Call a Pleb_Signer method and unwrap its result
+    async fn call<B>(method: &str, body: &B) -> anyhow::Result<String>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        let conn = zbus::Connection::session().await?;
+
+        let msg = conn.call_method(
+            Some("com.plebsigner.Signer"),
+            "/com/plebsigner/Signer",
+            Some("com.plebsigner.Signer1"),
+            method,
+            body,
+        ).await.map_err(|e| anyhow!("D-Bus call failed: {}", e))?;
+
+        let response: String = msg.body().deserialize()?;
+
+        eprintln!("[Nostr/PlebSigner] {} response: {}", method, response);
+
+        let parsed: PlebSignerResponse = serde_json::from_str(&response)?;
+
+        if !parsed.success {
+            return Err(anyhow!("Pleb_Signer error: {}", parsed.error.unwrap_or_else(|| "Unknown error".to_string())));
+        }
+
+        let result_json = parsed.result.ok_or_else(|| anyhow!("No result in response"))?;
+
+        // Result is double-encoded JSON string
+        Ok(serde_json::from_str(&result_json)?)
+    }
+}
+
+impl NostrSigner for PlebSigner {
+    fn get_public_key(&self) -> BoxFuture<'_, Result<PublicKey, PostError>> {
+        Box::pin(async move {
+            let pubkey = Self::call("GetPublicKey", &())
+                .await
+                .map_err(|e| signer_error("get pubkey", e))?;
+            PublicKey::from_hex(&pubkey).map_err(|e| signer_error("get pubkey", e))
+        })
+    }
+
+    fn sign_event(&self, unsigned: UnsignedEvent) -> BoxFuture<'_, Result<Event, PostError>> {
+        Box::pin(async move {
+            eprintln!("[Nostr] Requesting signature from Pleb_Signer...");
+            // Pleb_Signer fills in the pubkey and id itself
+            let unsigned_event = serde_json::json!({
+                "kind": unsigned.kind.as_u16(),
+                "content": unsigned.content,
+                "tags": unsigned.tags,
+                "created_at": unsigned.created_at.as_u64()
+            });
+            let signed_event_json = Self::call("SignEvent", &(unsigned_event.to_string(), APP_ID))
+                .await
+                .map_err(|e| signer_error("sign event", e))?;
+
+            eprintln!("[Nostr] Event signed successfully");
+
+            Event::from_json(&signed_event_json).map_err(|e| {
+                eprintln!("[Nostr] Failed to parse signed event: {}", e);
+                PostError::new(ErrorKind::Other, format!("Failed to parse signed event: {}", e))
+            })
+        })
+    }
+
+    fn nip44_encrypt<'a>(&'a self, public_key: &'a PublicKey, content: &'a str) -> BoxFuture<'a, Result<String, PostError>> {
+        Box::pin(async move {
+            Self::call("Nip44Encrypt", &(content, public_key.to_hex(), APP_ID))
+                .await
+                .map_err(|e| signer_error("encrypt", e))
+        })
+    }
+
+    fn nip44_decrypt<'a>(&'a self, public_key: &'a PublicKey, payload: &'a str) -> BoxFuture<'a, Result<String, PostError>> {
+        Box::pin(async move {
+            Self::call("Nip44Decrypt", &(payload, public_key.to_hex(), APP_ID))
+                .await
+                .map_err(|e| signer_error("decrypt", e))
+        })
+    }
+}
+
+/// NIP-46 remote signer such as nsecBunker or Amber, reached through relays
+pub struct BunkerSigner {
+    signer: NostrConnect,
+}
+
+impl BunkerSigner {
+    /// `uri` is a `bunker://` or `nostrconnect://` URI, `app_key` the key doh
+    /// identifies itself with. `timeout` bounds each request, including the
+    /// user approving it.
+    pub fn new(uri: &str, app_key: &str, timeout: Duration) -> Result<Self, PostError> {
+        let uri = NostrConnectURI::parse(uri).map_err(|e| {
+            PostError::new(ErrorKind::Auth, format!("Invalid bunker URI: {}", e))
+        })?;
+        let app_keys = Keys::parse(app_key).map_err(|e| {
+            PostError::new(ErrorKind::Auth, format!("Invalid remote signer app key: {}", e))
+        })?;
+        let signer = NostrConnect::new(uri, app_keys, timeout, None).map_err(|e| {
+            PostError::new(ErrorKind::Auth, format!("Failed to reach remote signer: {}", e))
+        })?;
+        Ok(Self { signer })
+    }
+}
+
+impl NostrSigner for BunkerSigner {
+    fn get_public_key(&self) -> BoxFuture<'_, Result<PublicKey, PostError>> {
+        Box::pin(async move {
+            SdkSigner::get_public_key(&self.signer)
+                .await
+                .map_err(|e| signer_error("get pubkey from remote signer", e))
+        })
+    }
+
+    fn sign_event(&self, unsigned: UnsignedEvent) -> BoxFuture<'_, Result<Event, PostError>> {
+        Box::pin(async move {
+            eprintln!("[Nostr] Requesting signature from remote signer...");
+            SdkSigner::sign_event(&self.signer, unsigned)
+                .await
+                .map_err(|e| signer_error("sign event", e))
+        })
+    }
+
+    fn nip44_encrypt<'a>(&'a self, public_key: &'a PublicKey, content: &'a str) -> BoxFuture<'a, Result<String, PostError>> {
+        Box::pin(async move {
+            SdkSigner::nip44_encrypt(&self.signer, public_key, content)
+                .await
+                .map_err(|e| signer_error("encrypt", e))
+        })
+    }
+
+    fn nip44_decrypt<'a>(&'a self, public_key: &'a PublicKey, payload: &'a str) -> BoxFuture<'a, Result<String, PostError>> {
+        Box::pin(async move {
+            SdkSigner::nip44_decrypt(&self.signer, public_key, payload)
+                .await
+                .map_err(|e| signer_error("decrypt", e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_connect::prelude::{NostrConnectKeys, NostrConnectRemoteSigner, NostrConnectSignerActions};
+    use nostr_relay_builder::MockRelay;
+    use nostr_sdk::prelude::{EventBuilder, nip46};
+
+    /// Approves every request, like a bunker the user has fully trusted
+    struct ApproveAll;
+
+    impl NostrConnectSignerActions for ApproveAll {
+        fn approve(&self, _public_key: &PublicKey, _request: &nip46::Request) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn bunker_signs_through_a_relay() {
+        let relay = MockRelay::run().await.unwrap();
+        let user = Keys::generate();
+        let keys = NostrConnectKeys {
+            signer: Keys::generate(),
+            user: user.clone(),
+        };
+        let remote = NostrConnectRemoteSigner::new(keys, [relay.url()], None, None).await.unwrap();
+        let uri = remote.bunker_uri().to_string();
+        tokio::spawn(async move { remote.serve(ApproveAll).await });
+
+        let app_key = super::super::generate_app_key().unwrap();
+        let signer = BunkerSigner::new(&uri, &app_key, Duration::from_secs(10)).unwrap();
+        assert_eq!(signer.get_public_key().await.unwrap(), user.public_key());
+
+        let unsigned = EventBuilder::text_note("Signed by the bunker", []).build(user.public_key());
+        let event = signer.sign_event(unsigned).await.unwrap();
+        assert_eq!(event.pubkey, user.public_key());
+        assert!(event.verify().is_ok());
+    }
+}
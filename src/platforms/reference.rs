@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: MIT
+
+//! Replies and quotes of existing posts, identified by their web URL
+
+use super::PlatformId;
+use serde::{Deserialize, Serialize};
+
+/// How a post refers to an existing one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReferenceKind {
+    Reply,
+    Quote,
+}
+
+/// A post to reply to or quote, as pasted by the user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostReference {
+    pub kind: ReferenceKind,
+    pub url: String,
+}
+
+impl PostReference {
+    pub fn new(kind: ReferenceKind, url: impl Into<String>) -> Self {
+        Self {
+            kind,
+            url: url.into().trim().to_string(),
+        }
+    }
+
+    /// Platform the referenced post lives on, if the URL is recognised
+    pub fn platform(&self) -> Option<PlatformId> {
+        parse(&self.url).map(|parsed| parsed.platform())
+    }
+}
+
+/// The parts of a recognised post URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsedUrl {
+    /// `bsky.app/profile/<handle or did>/post/<rkey>`
+    BlueSky { actor: String, rkey: String },
+    /// `x.com/<user>/status/<id>` (or twitter.com)
+    X { id: String },
+    /// `<instance>/@<user>/<id>`; only the user's own instance can map it to
+    /// a local id, so the full URL is kept
+    Mastodon { url: String },
+    /// `note1…`/`nevent1…`, bare, as a `nostr:` URI or under a web viewer
+    Nostr { bech32: String },
+}
+
+impl ParsedUrl {
+    pub fn platform(&self) -> PlatformId {
+        match self {
+            ParsedUrl::BlueSky { .. } => PlatformId::BlueSky,
+            ParsedUrl::X { .. } => PlatformId::X,
+            ParsedUrl::Mastodon { .. } => PlatformId::Mastodon,
+            ParsedUrl::Nostr { .. } => PlatformId::Nostr,
+        }
+    }
+}
+
+/// Recognise a post URL
+pub fn parse(url: &str) -> Option<ParsedUrl> {
+    let url = url.trim();
+
+    // Nostr identifiers can appear anywhere: bare, as nostr:, or in a viewer path
+    let last = url
+        .trim_start_matches("nostr:")
+        .rsplit('/')
+        .next()?
+        .split(['?', '#'])
+        .next()?;
+    if (last.starts_with("note1") || last.starts_with("nevent1"))
+        && last.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return Some(ParsedUrl::Nostr { bech32: last.to_string() });
+    }
+
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let rest = rest.split(['?', '#']).next()?;
+    let mut segments = rest.split('/').filter(|segment| !segment.is_empty());
+    let host = segments.next()?.trim_start_matches("www.").to_lowercase();
+    let path: Vec<&str> = segments.collect();
+
+    match (host.as_str(), path.as_slice()) {
+        ("bsky.app", ["profile", actor, "post", rkey]) => Some(ParsedUrl::BlueSky {
+            actor: actor.to_string(),
+            rkey: rkey.to_string(),
+        }),
+        ("x.com" | "twitter.com" | "mobile.twitter.com", [_, "status", id, ..])
+            if id.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            Some(ParsedUrl::X { id: id.to_string() })
+        }
+        (_, [user, id]) if user.starts_with('@') && id.chars().all(|c| c.is_ascii_digit()) => {
+            Some(ParsedUrl::Mastodon { url: url.to_string() })
+        }
+        (_, ["users", _, "statuses", id] | ["web", "statuses", id])
+            if id.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            Some(ParsedUrl::Mastodon { url: url.to_string() })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bluesky_posts() {
+        assert_eq!(
+            parse("https://bsky.app/profile/alice.bsky.social/post/3kabc?ref=x"),
+            Some(ParsedUrl::BlueSky {
+                actor: "alice.bsky.social".to_string(),
+                rkey: "3kabc".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_x_and_twitter_posts() {
+        let expected = Some(ParsedUrl::X { id: "1234".to_string() });
+        assert_eq!(parse("https://x.com/bob/status/1234"), expected);
+        assert_eq!(parse("https://www.twitter.com/bob/status/1234/photo/1"), expected);
+        assert_eq!(parse("https://x.com/bob/status/abc"), None);
+    }
+
+    #[test]
+    fn parses_mastodon_posts() {
+        for url in [
+            "https://mastodon.social/@carol/109876",
+            "https://mastodon.social/users/carol/statuses/109876",
+            "https://mastodon.social/web/statuses/109876",
+        ] {
+            assert_eq!(parse(url), Some(ParsedUrl::Mastodon { url: url.to_string() }));
+        }
+    }
+
+    #[test]
+    fn parses_nostr_ids_anywhere() {
+        let expected = Some(ParsedUrl::Nostr { bech32: "note1abc".to_string() });
+        assert_eq!(parse("note1abc"), expected);
+        assert_eq!(parse("nostr:note1abc"), expected);
+        assert_eq!(parse("https://njump.me/note1abc?x=1"), expected);
+        assert_eq!(
+            parse(" nevent1xyz "),
+            Some(ParsedUrl::Nostr { bech32: "nevent1xyz".to_string() })
+        );
+    }
+
+    #[test]
+    fn rejects_other_urls() {
+        assert_eq!(parse("https://example.com/some/page"), None);
+        assert_eq!(parse("not a url"), None);
+        assert_eq!(PostReference::new(ReferenceKind::Reply, "https://example.com").platform(), None);
+    }
+}
@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: MIT
+
+//! Registry of configured platform adapters
+
+use super::bluesky::BlueSkyPlatform;
+use super::mastodon::MastodonPlatform;
+use super::nostr::NostrPlatform;
+use super::twitter::TwitterPlatform;
+use super::{Platform, PlatformId};
+use crate::credentials::Credentials;
+use std::collections::HashMap;
+
+/// Platform adapters keyed by their [`PlatformId`]
+#[derive(Default)]
+pub struct PlatformRegistry {
+    adapters: HashMap<PlatformId, Box<dyn Platform>>,
+}
+
+impl PlatformRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Build a registry containing every platform that has credentials configured
+    pub fn from_credentials(credentials: &Credentials) -> Self {
+        let mut registry = Self::new();
+        
+        if let Some(platform) = TwitterPlatform::from_credentials(credentials) {
+            registry.register(Box::new(platform));
+        }
+        if let Some(platform) = BlueSkyPlatform::from_credentials(credentials) {
+            registry.register(Box::new(platform));
+        }
+        if let Some(platform) = NostrPlatform::from_credentials(credentials) {
+            registry.register(Box::new(platform));
+        }
+        if let Some(platform) = MastodonPlatform::from_credentials(credentials) {
+            registry.register(Box::new(platform));
+        }
+        
+        registry
+    }
+    
+    /// Register an adapter, replacing any adapter with the same id
+    pub fn register(&mut self, adapter: Box<dyn Platform>) {
+        self.adapters.insert(adapter.id(), adapter);
+    }
+    
+    pub fn get(&self, id: PlatformId) -> Option<&dyn Platform> {
+        self.adapters.get(&id).map(|adapter| adapter.as_ref())
+    }
+}
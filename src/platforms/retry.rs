@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: MIT
+
+//! Shared retry layer for platform HTTP requests
+
+use super::PostError;
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+/// Whether a request may safely be sent more than once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idempotency {
+    /// Repeating the request has no additional effect (logins, media uploads,
+    /// requests carrying an idempotency key)
+    Idempotent,
+    /// Repeating the request could publish twice, so it is only retried when
+    /// the server definitely did not process it
+    NonIdempotent,
+}
+
+/// How often and how long to retry a failed request
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for each further retry
+    pub base_delay: Duration,
+    /// Longest we are willing to wait between attempts. A server asking us to
+    /// wait longer than this is treated as a final answer.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// Send a request, retrying transient failures.
+    ///
+    /// `build` is called once per attempt because multipart bodies can't be
+    /// cloned. Unsuccessful responses that aren't worth retrying are returned
+    /// as-is so the caller can report them.
+    pub async fn send<F>(
+        &self,
+        label: &str,
+        idempotency: Idempotency,
+        mut build: F,
+    ) -> Result<Response, PostError>
+    where
+        F: FnMut() -> Result<RequestBuilder, PostError>,
+    {
+        let mut attempt = 1;
+        loop {
+            let can_retry = attempt < self.max_attempts;
+
+            match build()?.send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    if status.is_success() || !can_retry || !is_retryable_status(status, idempotency) {
+                        return Ok(response);
+                    }
+
+                    let delay = retry_delay(status, response.headers())
+                        .unwrap_or_else(|| self.backoff(attempt));
+                    if delay > self.max_delay {
+                        eprintln!("[Retry] {} asked to wait {}s, giving up", label, delay.as_secs());
+                        return Ok(response);
+                    }
+
+                    eprintln!(
+                        "[Retry] {} returned {}, retrying in {}ms (attempt {}/{})",
+                        label, status, delay.as_millis(), attempt + 1, self.max_attempts
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => {
+                    if !can_retry || !is_retryable_error(&e, idempotency) {
+                        return Err(e.into());
+                    }
+
+                    let delay = self.backoff(attempt);
+                    eprintln!(
+                        "[Retry] {} failed: {}, retrying in {}ms (attempt {}/{})",
+                        label, e, delay.as_millis(), attempt + 1, self.max_attempts
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+            }
+
+            attempt += 1;
+        }
+    }
+}
+
+/// Status codes worth another attempt, because the server definitely
+/// didn't process the request or processing it again is harmless
+pub(crate) fn is_retryable_status(status: StatusCode, idempotency: Idempotency) -> bool {
+    match status.as_u16() {
+        // The request was refused before doing anything
+        429 => true,
+        // The request may or may not have been processed
+        408 | 500 | 502 | 503 | 504 => idempotency == Idempotency::Idempotent,
+        _ => false,
+    }
+}
+
+/// Transport errors worth another attempt
+fn is_retryable_error(e: &reqwest::Error, idempotency: Idempotency) -> bool {
+    if e.is_connect() {
+        // Never reached the server, so nothing can have been committed
+        return true;
+    }
+    (e.is_timeout() || e.is_request()) && idempotency == Idempotency::Idempotent
+}
+
+/// How long the server asked us to wait, from `Retry-After` or, for 429s, a
+/// rate limit reset header
+fn retry_delay(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let now = chrono::Utc::now();
+
+    if let Some(value) = header("retry-after") {
+        // Either delta-seconds or an HTTP date
+        if let Ok(seconds) = value.trim().parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        if let Ok(date) = chrono::DateTime::parse_from_rfc2822(value) {
+            return Some((date.with_timezone(&chrono::Utc) - now).to_std().unwrap_or_default());
+        }
+    }
+
+    // Rate limit headers are sent on every response, so they only mean
+    // "wait" once we've actually been limited. X sends epoch seconds,
+    // BlueSky `ratelimit-reset` epoch seconds and Mastodon an ISO 8601 timestamp.
+    if status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let reset = header("x-rate-limit-reset")
+        .or_else(|| header("ratelimit-reset"))?
+        .trim();
+    let reset_at = match reset.parse::<i64>() {
+        Ok(epoch) => chrono::DateTime::from_timestamp(epoch, 0)?,
+        Err(_) => chrono::DateTime::parse_from_rfc3339(reset).ok()?.with_timezone(&chrono::Utc),
+    };
+    Some((reset_at - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    /// Allow for the clock moving between building the headers and parsing them
+    fn about(delay: Option<Duration>, seconds: u64) -> bool {
+        delay.is_some_and(|delay| delay <= Duration::from_secs(seconds) && delay + Duration::from_secs(2) >= Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let headers = headers(&[("retry-after", "7".to_string())]);
+        assert_eq!(retry_delay(StatusCode::SERVICE_UNAVAILABLE, &headers), Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn retry_after_date() {
+        let date = (chrono::Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let headers = headers(&[("retry-after", date)]);
+        assert!(about(retry_delay(StatusCode::TOO_MANY_REQUESTS, &headers), 60));
+    }
+
+    #[test]
+    fn retry_after_in_the_past_means_now() {
+        let date = (chrono::Utc::now() - chrono::Duration::seconds(60)).to_rfc2822();
+        let headers = headers(&[("retry-after", date)]);
+        assert_eq!(retry_delay(StatusCode::TOO_MANY_REQUESTS, &headers), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn rate_limit_reset_epoch() {
+        let reset = (chrono::Utc::now().timestamp() + 30).to_string();
+        let x = headers(&[("x-rate-limit-reset", reset.clone())]);
+        let bluesky = headers(&[("ratelimit-reset", reset)]);
+        assert!(about(retry_delay(StatusCode::TOO_MANY_REQUESTS, &x), 30));
+        assert!(about(retry_delay(StatusCode::TOO_MANY_REQUESTS, &bluesky), 30));
+    }
+
+    #[test]
+    fn rate_limit_reset_timestamp() {
+        let reset = (chrono::Utc::now() + chrono::Duration::seconds(45)).to_rfc3339();
+        let headers = headers(&[("x-rate-limit-reset", reset)]);
+        assert!(about(retry_delay(StatusCode::TOO_MANY_REQUESTS, &headers), 45));
+    }
+
+    #[test]
+    fn rate_limit_reset_ignored_unless_limited() {
+        let reset = (chrono::Utc::now().timestamp() + 30).to_string();
+        let headers = headers(&[("x-rate-limit-reset", reset)]);
+        assert_eq!(retry_delay(StatusCode::SERVICE_UNAVAILABLE, &headers), None);
+        assert_eq!(retry_delay(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn only_idempotent_requests_retry_server_errors() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS, Idempotency::NonIdempotent));
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY, Idempotency::Idempotent));
+        assert!(!is_retryable_status(StatusCode::BAD_GATEWAY, Idempotency::NonIdempotent));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST, Idempotency::Idempotent));
+    }
+}
@@ -1,18 +1,41 @@
 // SPDX-License-Identifier: MIT
 
-//! X/Twitter platform adapter using OAuth 1.0a
+//! X/Twitter platform adapter using OAuth 2.0 user tokens or OAuth 1.0a keys
 
-use super::{Platform, Post, PostResult};
-use anyhow::{Result, anyhow};
+use super::retry::{Idempotency, RetryPolicy};
+use super::reference::{self, ParsedUrl};
+use super::{ErrorKind, Platform, PlatformId, Post, PostError, PostOutcome, RemoteId, ReplyRef};
+use crate::credentials::Credentials;
+use crate::oauth::{self, OAuthTokens};
+use futures_util::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use base64::Engine;
 
+const DEFAULT_API_URL: &str = "https://api.twitter.com";
+const DEFAULT_UPLOAD_URL: &str = "https://upload.twitter.com";
+
+/// X rejects longer image descriptions
+const MAX_ALT_TEXT_CHARS: usize = 1000;
+
 pub struct TwitterPlatform {
+    api_url: String,
+    upload_url: String,
     consumer_key: String,
     consumer_secret: String,
     access_token: String,
     access_secret: String,
+    oauth2: Option<OAuth2>,
+    retry: RetryPolicy,
+}
+
+/// OAuth 2.0 sign-in. Clones share the tokens, so a refresh is seen by
+/// every later request.
+#[derive(Clone)]
+struct OAuth2 {
+    client_id: String,
+    tokens: Arc<tokio::sync::Mutex<OAuthTokens>>,
 }
 
 #[derive(Deserialize)]
@@ -20,11 +43,41 @@ struct TwitterMediaResponse {
     media_id_string: String,
 }
 
+/// `POST /2/media/upload`, the upload endpoint OAuth 2.0 tokens can use
+#[derive(Deserialize)]
+struct MediaUploadResponse {
+    data: MediaUploadData,
+}
+
+#[derive(Deserialize)]
+struct MediaUploadData {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct TweetResponse {
+    data: TweetData,
+}
+
+#[derive(Deserialize)]
+struct TweetData {
+    id: String,
+}
+
 #[derive(Serialize)]
 struct TweetRequest {
     text: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     media: Option<MediaAttachment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply: Option<TweetReply>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quote_tweet_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TweetReply {
+    in_reply_to_tweet_id: String,
 }
 
 #[derive(Serialize)]
@@ -40,10 +93,89 @@ impl TwitterPlatform {
         access_secret: String,
     ) -> Self {
         Self {
+            api_url: DEFAULT_API_URL.to_string(),
+            upload_url: DEFAULT_UPLOAD_URL.to_string(),
             consumer_key,
             consumer_secret,
             access_token,
             access_secret,
+            oauth2: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+    
+    /// Create the adapter if X credentials are configured
+    pub fn from_credentials(credentials: &Credentials) -> Option<Self> {
+        if !credentials.has_twitter() {
+            return None;
+        }
+        if let (Some(client_id), Some(tokens)) = (&credentials.twitter_client_id, &credentials.twitter_oauth) {
+            return Some(
+                Self::new(String::new(), String::new(), String::new(), String::new())
+                    .with_oauth2(client_id.clone(), tokens.clone()),
+            );
+        }
+        Some(Self::new(
+            credentials.twitter_consumer_key.clone()?,
+            credentials.twitter_consumer_secret.clone()?,
+            credentials.twitter_access_token.clone()?,
+            credentials.twitter_access_secret.clone()?,
+        ))
+    }
+    
+    /// Override the API and media upload hosts (e.g. a mock server)
+    pub fn with_base_urls(mut self, api_url: String, upload_url: String) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self.upload_url = upload_url.trim_end_matches('/').to_string();
+        self
+    }
+    
+    /// Authenticate with OAuth 2.0 user tokens instead of OAuth 1.0a keys
+    pub fn with_oauth2(mut self, client_id: String, tokens: OAuthTokens) -> Self {
+        self.oauth2 = Some(OAuth2 {
+            client_id,
+            tokens: Arc::new(tokio::sync::Mutex::new(tokens)),
+        });
+        self
+    }
+    
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    
+    /// Current OAuth 2.0 access token, refreshed if it has expired, or
+    /// `None` when signing requests with OAuth 1.0a
+    async fn bearer_token(&self) -> Result<Option<String>, PostError> {
+        let Some(oauth2) = &self.oauth2 else {
+            return Ok(None);
+        };
+        let mut tokens = oauth2.tokens.lock().await;
+        
+        if tokens.is_expired() {
+            // Another post may already have refreshed them
+            if let Some(stored) = Credentials::load().ok().and_then(|credentials| credentials.twitter_oauth) {
+                *tokens = stored;
+            }
+        }
+        if tokens.is_expired() {
+            eprintln!("[Twitter] Refreshing access token");
+            *tokens = oauth::x::refresh(&oauth2.client_id, &tokens)
+                .await
+                .map_err(|e| PostError::new(ErrorKind::Auth, format!("X sign-in expired, sign in again: {}", e)))?;
+            let refreshed = tokens.clone();
+            if let Err(e) = Credentials::update(|credentials| credentials.twitter_oauth = Some(refreshed)) {
+                eprintln!("[Twitter] Failed to store refreshed tokens: {}", e);
+            }
+        }
+        Ok(Some(tokens.access_token.clone()))
+    }
+    
+    /// `Authorization` header for a request
+    fn authorization(&self, method: &str, url: &str, bearer_token: Option<&str>) -> String {
+        match bearer_token {
+            Some(token) => format!("Bearer {}", token),
+            None => self.generate_oauth_header(method, url, None),
         }
     }
     
@@ -143,108 +275,240 @@ impl TwitterPlatform {
 }
 
 impl Platform for TwitterPlatform {
-    fn name(&self) -> &'static str {
-        "X"
+    fn id(&self) -> PlatformId {
+        PlatformId::X
     }
     
-    async fn is_authenticated(&self) -> bool {
-        !self.consumer_key.is_empty()
-            && !self.consumer_secret.is_empty()
-            && !self.access_token.is_empty()
-            && !self.access_secret.is_empty()
+    fn is_authenticated(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            self.oauth2.is_some()
+                || !self.consumer_key.is_empty()
+                && !self.consumer_secret.is_empty()
+                && !self.access_token.is_empty()
+                && !self.access_secret.is_empty()
+        })
     }
     
-    async fn post(&self, post: &Post) -> Result<PostResult> {
+    fn post<'a>(&'a self, post: &'a Post) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+        Box::pin(self.publish(post))
+    }
+    
+    fn delete<'a>(&'a self, remote_id: &'a RemoteId) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+        Box::pin(self.remove(remote_id))
+    }
+    
+    fn resolve<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<ReplyRef, PostError>> {
+        Box::pin(async move {
+            // The status id in the URL is all X needs
+            match reference::parse(url) {
+                Some(ParsedUrl::X { id }) => Ok(ReplyRef {
+                    root: RemoteId::Tweet(id.clone()),
+                    parent: RemoteId::Tweet(id),
+                }),
+                _ => Err(PostError::new(ErrorKind::Validation, format!("Not an X post: {}", url))),
+            }
+        })
+    }
+}
+
+impl TwitterPlatform {
+    async fn publish(&self, post: &Post) -> Result<PostOutcome, PostError> {
         use crate::image_utils;
         
+        let reply = match &post.reply {
+            None => None,
+            Some(ReplyRef { parent: RemoteId::Tweet(id), .. }) => Some(id.clone()),
+            Some(_) => return Err(PostError::new(ErrorKind::Validation, "Can only reply to a tweet")),
+        };
+        let quote_tweet_id = match &post.quote {
+            None => None,
+            Some(RemoteId::Tweet(id)) => Some(id.clone()),
+            Some(_) => return Err(PostError::new(ErrorKind::Validation, "Can only quote a tweet")),
+        };
+        
         // Upload images first if any
         let mut media_ids = Vec::new();
-        for (i, image_bytes) in post.images.iter().enumerate() {
-            eprintln!("[Twitter] Uploading image {} ({} bytes)", i + 1, image_bytes.len());
+        for (i, image) in post.images.iter().enumerate() {
+            eprintln!("[Twitter] Uploading image {} ({} bytes)", i + 1, image.bytes.len());
             
             // Process image - Twitter supports up to 5MB
             let processor = image_utils::ImageProcessor::new()
                 .with_max_size(5_000_000); // 5MB
-            let processed_bytes = processor.process(image_bytes)?;
+            let processed_bytes = processor.process(&image.bytes)
+                .map_err(|e| PostError::new(ErrorKind::Media, format!("Failed to process image: {}", e)))?;
             
             match self.upload_media(&processed_bytes).await {
                 Ok(media_id) => {
                     eprintln!("[Twitter] Image {} uploaded: {}", i + 1, media_id);
+                    if !image.alt.is_empty() {
+                        self.set_alt_text(&media_id, &image.alt).await.map_err(PostError::into_media_error)?;
+                    }
                     media_ids.push(media_id);
                 }
                 Err(e) => {
                     eprintln!("[Twitter] Failed to upload image {}: {}", i + 1, e);
-                    return Ok(PostResult::Error {
-                        message: format!("Failed to upload image: {}", e),
-                    });
+                    return Err(e.into_media_error());
                 }
             }
         }
         
-        let url = "https://api.twitter.com/2/tweets";
+        let url = format!("{}/2/tweets", self.api_url);
+        let bearer_token = self.bearer_token().await?;
         
         let tweet = TweetRequest {
             text: post.text.clone(),
             media: if media_ids.is_empty() {
                 None
             } else {
-                Some(MediaAttachment { media_ids })
+                Some(MediaAttachment { media_ids: media_ids.clone() })
             },
+            reply: reply.map(|in_reply_to_tweet_id| TweetReply { in_reply_to_tweet_id }),
+            quote_tweet_id,
         };
         
-        let oauth_header = self.generate_oauth_header("POST", url, None);
-        
+        // A tweet can't be deduplicated server-side, so only retry when X
+        // refused it outright (rate limits, connection failures)
         let client = reqwest::Client::new();
-        let response = client
-            .post(url)
-            .header("Authorization", oauth_header)
-            .header("Content-Type", "application/json")
-            .json(&tweet)
-            .send()
+        let response = self
+            .retry
+            .send("X tweet", Idempotency::NonIdempotent, || {
+                Ok(client
+                    .post(&url)
+                    .header("Authorization", self.authorization("POST", &url, bearer_token.as_deref()))
+                    .header("Content-Type", "application/json")
+                    .json(&tweet))
+            })
             .await?;
         
-        if response.status().is_success() {
-            Ok(PostResult::Success {
-                url: Some("Posted to X".to_string()),
+        let status = response.status();
+        if status.is_success() {
+            let tweet: TweetResponse = response.json().await?;
+            Ok(PostOutcome {
+                // The status link works without knowing the account's username
+                url: Some(format!("https://x.com/i/web/status/{}", tweet.data.id)),
+                remote_id: Some(RemoteId::Tweet(tweet.data.id)),
+                media_ids,
+                ..PostOutcome::success(PlatformId::X)
             })
         } else {
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            Ok(PostResult::Error {
-                message: format!("Failed to post: {}", error_text),
-            })
+            Err(PostError::from_status(status, &error_text, Idempotency::NonIdempotent))
         }
     }
 }
 
 impl TwitterPlatform {
-    async fn upload_media(&self, image_bytes: &[u8]) -> Result<String> {
-        use crate::image_utils;
+    async fn remove(&self, remote_id: &RemoteId) -> Result<PostOutcome, PostError> {
+        let RemoteId::Tweet(id) = remote_id else {
+            return Err(PostError::new(ErrorKind::Validation, "Not a tweet"));
+        };
         
-        let url = "https://upload.twitter.com/1.1/media/upload.json";
-        let mime_type = image_utils::get_mime_type(image_bytes)?;
+        let url = format!("{}/2/tweets/{}", self.api_url, id);
+        let bearer_token = self.bearer_token().await?;
+        eprintln!("[Twitter] Deleting tweet {}", id);
         
-        let oauth_header = self.generate_oauth_header("POST", url, None);
+        // Deleting twice has no further effect
+        let client = reqwest::Client::new();
+        let response = self
+            .retry
+            .send("X delete", Idempotency::Idempotent, || {
+                Ok(client
+                    .delete(&url)
+                    .header("Authorization", self.authorization("DELETE", &url, bearer_token.as_deref())))
+            })
+            .await?;
         
-        let part = reqwest::multipart::Part::bytes(image_bytes.to_vec())
-            .mime_str(&mime_type)?;
+        let status = response.status();
+        if status.is_success() || status == reqwest::StatusCode::NOT_FOUND {
+            Ok(PostOutcome {
+                remote_id: Some(remote_id.clone()),
+                ..PostOutcome::success(PlatformId::X)
+            })
+        } else {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            Err(PostError::from_status(status, &error_text, Idempotency::Idempotent))
+        }
+    }
+    
+    async fn upload_media(&self, image_bytes: &[u8]) -> Result<String, PostError> {
+        use crate::image_utils;
         
-        let form = reqwest::multipart::Form::new()
-            .part("media", part);
+        let bearer_token = self.bearer_token().await?;
+        // OAuth 2.0 tokens only work with the v2 upload endpoint
+        let url = match bearer_token {
+            Some(_) => format!("{}/2/media/upload", self.api_url),
+            None => format!("{}/1.1/media/upload.json", self.upload_url),
+        };
+        let mime_type = image_utils::get_mime_type(image_bytes)
+            .map_err(|e| PostError::new(ErrorKind::Media, e.to_string()))?;
         
+        // An upload that is repeated only leaves an unused media id behind
         let client = reqwest::Client::new();
-        let response = client
-            .post(url)
-            .header("Authorization", oauth_header)
-            .multipart(form)
-            .send()
+        let response = self
+            .retry
+            .send("X media upload", Idempotency::Idempotent, || {
+                let part = reqwest::multipart::Part::bytes(image_bytes.to_vec())
+                    .mime_str(&mime_type)?;
+                
+                let mut form = reqwest::multipart::Form::new()
+                    .part("media", part);
+                if bearer_token.is_some() {
+                    form = form.text("media_category", "tweet_image");
+                }
+                
+                Ok(client
+                    .post(&url)
+                    .header("Authorization", self.authorization("POST", &url, bearer_token.as_deref()))
+                    .multipart(form))
+            })
             .await?;
         
-        if !response.status().is_success() {
+        let status = response.status();
+        if !status.is_success() {
             let error_text = response.text().await?;
-            return Err(anyhow!("Failed to upload media: {}", error_text));
+            return Err(PostError::from_status(status, &error_text, Idempotency::Idempotent));
         }
         
+        if bearer_token.is_some() {
+            let media_response: MediaUploadResponse = response.json().await?;
+            return Ok(media_response.data.id);
+        }
         let media_response: TwitterMediaResponse = response.json().await?;
         Ok(media_response.media_id_string)
     }
+    
+    /// Describe an uploaded image for screen readers
+    async fn set_alt_text(&self, media_id: &str, alt: &str) -> Result<(), PostError> {
+        let alt: String = alt.chars().take(MAX_ALT_TEXT_CHARS).collect();
+        let bearer_token = self.bearer_token().await?;
+        let (url, body) = match bearer_token {
+            Some(_) => (
+                format!("{}/2/media/metadata", self.api_url),
+                serde_json::json!({ "id": media_id, "metadata": { "alt_text": { "text": alt } } }),
+            ),
+            None => (
+                format!("{}/1.1/media/metadata/create.json", self.upload_url),
+                serde_json::json!({ "media_id": media_id, "alt_text": { "text": alt } }),
+            ),
+        };
+        
+        // Setting the same description twice has no further effect
+        let client = reqwest::Client::new();
+        let response = self
+            .retry
+            .send("X alt text", Idempotency::Idempotent, || {
+                Ok(client
+                    .post(&url)
+                    .header("Authorization", self.authorization("POST", &url, bearer_token.as_deref()))
+                    .json(&body))
+            })
+            .await?;
+        
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(PostError::from_status(status, &error_text, Idempotency::Idempotent));
+        }
+        Ok(())
+    }
 }
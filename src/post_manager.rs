@@ -2,159 +2,599 @@
 
 //! Post manager for coordinating multi-platform posting
 
+use crate::config::Config;
 use crate::credentials::Credentials;
-use crate::platforms::{Platform, Post, PostResult};
-use crate::platforms::nostr::{NostrPlatform, NostrAuth};
-use crate::platforms::bluesky::BlueSkyPlatform;
-use crate::platforms::twitter::TwitterPlatform;
-use crate::platforms::mastodon::MastodonPlatform;
+use crate::history::HistoryTarget;
+use crate::outbox::{Outbox, OutboxEntry};
+use crate::platforms::nostr::{NostrPlatform, PowProgress};
+use crate::platforms::{
+    EditMethod, ErrorKind, Platform, PlatformId, PlatformRegistry, Post, PostError, PostImage,
+    PostOutcome, ReferenceKind, RemoteId, ReplyRef,
+};
+use crate::thread;
+use futures_util::future::BoxFuture;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How often a Nostr post is checked for proof-of-work mining, which
+/// doesn't count against its timeout
+const MINING_CHECK: Duration = Duration::from_millis(250);
+
+/// How long each platform may take before its post is cancelled
+#[derive(Debug, Clone, Copy)]
+pub struct PlatformTimeouts {
+    pub x: Duration,
+    pub bluesky: Duration,
+    pub nostr: Duration,
+    pub mastodon: Duration,
+}
+
+impl PlatformTimeouts {
+    fn get(&self, platform: PlatformId) -> Duration {
+        match platform {
+            PlatformId::Nostr => self.nostr,
+            PlatformId::BlueSky => self.bluesky,
+            PlatformId::X => self.x,
+            PlatformId::Mastodon => self.mastodon,
+        }
+    }
+}
+
+impl Default for PlatformTimeouts {
+    fn default() -> Self {
+        Self::from(&Config::default())
+    }
+}
+
+impl From<&Config> for PlatformTimeouts {
+    fn from(config: &Config) -> Self {
+        Self {
+            x: Duration::from_secs(config.x_timeout_secs),
+            bluesky: Duration::from_secs(config.bluesky_timeout_secs),
+            nostr: Duration::from_secs(config.nostr_timeout_secs),
+            mastodon: Duration::from_secs(config.mastodon_timeout_secs),
+        }
+    }
+}
 
 pub struct PostManager {
-    credentials: Credentials,
+    registry: PlatformRegistry,
+    timeouts: PlatformTimeouts,
+    pow: PowProgress,
 }
 
 impl PostManager {
     pub fn new(credentials: Credentials) -> Self {
-        Self { credentials }
+        Self::with_pow_progress(credentials, PowProgress::default())
+    }
+    
+    /// Like [`Self::new`], with Nostr proof-of-work progress reported to
+    /// `pow` so it can be shown and cancelled
+    pub fn with_pow_progress(credentials: Credentials, pow: PowProgress) -> Self {
+        let mut registry = PlatformRegistry::from_credentials(&credentials);
+        if let Some(platform) = NostrPlatform::from_credentials(&credentials) {
+            registry.register(Box::new(platform.with_pow_progress(pow.clone())));
+        }
+        Self {
+            pow,
+            ..Self::with_registry(registry)
+        }
+    }
+    
+    /// Create a manager over an explicit set of adapters
+    pub fn with_registry(registry: PlatformRegistry) -> Self {
+        Self {
+            registry,
+            timeouts: PlatformTimeouts::default(),
+            pow: PowProgress::default(),
+        }
+    }
+    
+    pub fn with_timeouts(mut self, timeouts: PlatformTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+    
+    /// Post to all selected platforms concurrently.
+    ///
+    /// Results are returned in the same order as `platforms`.
+    pub async fn post(&self, post: Post, platforms: Vec<PlatformId>) -> Vec<PostOutcome> {
+        let tasks = platforms
+            .into_iter()
+            .map(|platform| self.post_to(platform, &post));
+        
+        futures_util::future::join_all(tasks).await
     }
     
-    pub async fn post(
+    /// Delete published posts from their platforms concurrently.
+    ///
+    /// Results are returned in the same order as `targets`.
+    pub async fn delete(&self, targets: Vec<(PlatformId, RemoteId)>) -> Vec<PostOutcome> {
+        let tasks = targets
+            .iter()
+            .map(|(platform, remote_id)| self.delete_from(*platform, remote_id));
+        
+        futures_util::future::join_all(tasks).await
+    }
+    
+    /// How an edit would be applied on a platform, if it is configured
+    pub fn edit_method(&self, platform: PlatformId) -> Option<EditMethod> {
+        self.registry.get(platform).map(|adapter| adapter.edit_method())
+    }
+    
+    /// Replace published posts with new content concurrently.
+    ///
+    /// Results are returned in the same order as `targets`.
+    pub async fn edit(
         &self,
         text: String,
-        images: Vec<Vec<u8>>,
-        platforms: Vec<String>,
-    ) -> Vec<(String, bool, String)> {
-        let post = Post { text, images };
-        let mut results = Vec::new();
+        images: Vec<PostImage>,
+        targets: Vec<HistoryTarget>,
+    ) -> Vec<PostOutcome> {
+        let post = Post::new(text, images);
+        
+        let tasks = targets.iter().map(|target| async {
+            match self.registry.get(target.platform) {
+                Some(adapter) => self.run(target.platform, adapter.edit(target, &post)).await,
+                None => Self::not_configured(target.platform),
+            }
+        });
+        
+        futures_util::future::join_all(tasks).await
+    }
+    
+    /// Retry every outbox entry that is due, removing the ones that get through.
+    ///
+    /// Returns the outcome of each attempt along with its outbox entry.
+    pub async fn flush_outbox(&self, outbox: &Outbox) -> Vec<(OutboxEntry, PostOutcome)> {
+        let now = chrono::Utc::now();
+        let mut outcomes = Vec::new();
         
-        for platform_name in platforms {
-            let result = match platform_name.as_str() {
-                "Nostr" => self.post_nostr(&post).await,
-                "BlueSky" => self.post_bluesky(&post).await,
-                "X" => self.post_twitter(&post).await,
-                "Mastodon" => self.post_mastodon(&post).await,
-                _ => continue,
+        for mut entry in outbox.list().into_iter().filter(|entry| entry.is_due(now)) {
+            match outbox.start_sending(&mut entry) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    eprintln!("[Outbox] Failed to claim {}: {}", entry.id, e);
+                    continue;
+                }
+            }
+            
+            let images = match outbox.load_images(&entry) {
+                Ok(images) => images,
+                Err(e) => {
+                    eprintln!("[Outbox] Failed to load images for {}: {}", entry.id, e);
+                    let error = PostError::new(ErrorKind::Media, format!("Failed to load queued images: {}", e));
+                    let _ = outbox.record_failure(&mut entry, &error);
+                    continue;
+                }
             };
             
-            results.push(result);
+            let post = Post {
+                reference: entry.reference.clone(),
+                thread: entry.thread,
+                delivery_id: entry.delivery_id.clone(),
+                ..Post::new(entry.text.clone(), images)
+            };
+            let outcome = self.post_to(entry.platform, &post).await;
+            
+            let result = match &outcome.error {
+                None => outbox.delivered(&entry),
+                Some(error) if outcome.partial => outbox.record_partial(&mut entry, error),
+                Some(error) => outbox.record_failure(&mut entry, error),
+            };
+            if let Err(e) = result {
+                eprintln!("[Outbox] Failed to update {}: {}", entry.id, e);
+            }
+            
+            outcomes.push((entry, outcome));
         }
         
-        results
+        outcomes
     }
     
-    async fn post_nostr(&self, post: &Post) -> (String, bool, String) {
-        if !self.credentials.has_nostr() {
-            return ("Nostr".to_string(), false, "Not configured".to_string());
+    /// Post to a single platform, cancelling it if it exceeds its timeout
+    async fn post_to(&self, platform: PlatformId, post: &Post) -> PostOutcome {
+        match self.registry.get(platform) {
+            Some(adapter) => self.run(platform, Box::pin(publish(adapter, post))).await,
+            None => Self::not_configured(platform),
         }
-        
-        let auth = if self.credentials.nostr_use_pleb_signer {
-            NostrAuth::PlebSigner
-        } else {
-            NostrAuth::Nsec(self.credentials.nostr_nsec.clone().unwrap_or_default())
-        };
-        
-        let relays = if self.credentials.nostr_relays.is_empty() {
-            vec![
-                "wss://relay.primal.net".to_string(),
-                "wss://relay.damus.io".to_string(),
-                "wss://relay.pleb.one".to_string(),
-            ]
-        } else {
-            self.credentials.nostr_relays.clone()
-        };
-        
-        let platform = NostrPlatform::new(
-            auth,
-            relays,
-            self.credentials.nostr_image_host_url.clone(),
-        );
-        
-        match platform.post(post).await {
-            Ok(PostResult::Success { url }) => {
-                eprintln!("[Nostr] Success: {}", url.as_ref().unwrap_or(&"Posted successfully".to_string()));
-                ("Nostr".to_string(), true, url.unwrap_or_else(|| "Posted successfully".to_string()))
+    }
+    
+    /// Delete from a single platform, cancelling it if it exceeds its timeout
+    async fn delete_from(&self, platform: PlatformId, remote_id: &RemoteId) -> PostOutcome {
+        match self.registry.get(platform) {
+            Some(adapter) => self.run(platform, adapter.delete(remote_id)).await,
+            None => Self::not_configured(platform),
+        }
+    }
+    
+    fn not_configured(platform: PlatformId) -> PostOutcome {
+        PostOutcome::failure(
+            platform,
+            PostError::new(ErrorKind::NotConfigured, "Not configured"),
+        )
+    }
+    
+    /// Run an adapter call under the platform's timeout
+    async fn run(
+        &self,
+        platform: PlatformId,
+        call: BoxFuture<'_, Result<PostOutcome, PostError>>,
+    ) -> PostOutcome {
+        let timeout = self.timeouts.get(platform);
+        match self.within_timeout(platform, timeout, call).await {
+            Ok(Ok(outcome)) => {
+                match &outcome.error {
+                    None => eprintln!("[{}] Success: {}", platform, outcome.url.as_deref().unwrap_or("done")),
+                    Some(e) => eprintln!("[{}] Partially done ({:?}): {}", platform, e.kind, e),
+                }
+                outcome
             }
-            Ok(PostResult::Error { message }) => {
-                eprintln!("[Nostr] Error: {}", message);
-                ("Nostr".to_string(), false, message)
+            Ok(Err(e)) => {
+                eprintln!("[{}] Error ({:?}): {}", platform, e.kind, e);
+                PostOutcome::failure(platform, e)
             }
-            Err(e) => {
-                eprintln!("[Nostr] Exception: {}", e);
-                ("Nostr".to_string(), false, format!("Error: {}", e))
+            Err(_) => {
+                eprintln!("[{}] Timed out after {}s", platform, timeout.as_secs());
+                PostOutcome::failure(
+                    platform,
+                    PostError::new(ErrorKind::Timeout, format!("Timed out after {}s", timeout.as_secs())),
+                )
             }
         }
     }
     
-    async fn post_bluesky(&self, post: &Post) -> (String, bool, String) {
-        if !self.credentials.has_bluesky() {
-            return ("BlueSky".to_string(), false, "Not configured".to_string());
+    /// Await `call` for at most `timeout`. Time a Nostr post spends mining
+    /// proof of work doesn't count, since mining can take arbitrarily long
+    /// and the note hasn't been sent anywhere yet.
+    async fn within_timeout(
+        &self,
+        platform: PlatformId,
+        timeout: Duration,
+        mut call: BoxFuture<'_, Result<PostOutcome, PostError>>,
+    ) -> Result<Result<PostOutcome, PostError>, tokio::time::error::Elapsed> {
+        if platform != PlatformId::Nostr {
+            return tokio::time::timeout(timeout, call).await;
         }
         
-        let platform = BlueSkyPlatform::new(
-            self.credentials.bluesky_handle.clone().unwrap(),
-            self.credentials.bluesky_app_password.clone().unwrap(),
-        );
-        
-        match platform.post(post).await {
-            Ok(PostResult::Success { url }) => {
-                eprintln!("[BlueSky] Success: {}", url.as_ref().unwrap_or(&"Posted successfully".to_string()));
-                ("BlueSky".to_string(), true, url.unwrap_or_else(|| "Posted successfully".to_string()))
-            }
-            Ok(PostResult::Error { message }) => {
-                eprintln!("[BlueSky] Error: {}", message);
-                ("BlueSky".to_string(), false, message)
-            }
-            Err(e) => {
-                eprintln!("[BlueSky] Exception: {}", e);
-                ("BlueSky".to_string(), false, format!("Error: {}", e))
+        let mut deadline = Instant::now() + timeout;
+        loop {
+            let check = (Instant::now() + MINING_CHECK).min(deadline);
+            match tokio::time::timeout_at(check, &mut call).await {
+                Ok(result) => return Ok(result),
+                Err(_) if self.pow.mining().is_some() => deadline += MINING_CHECK,
+                Err(elapsed) if Instant::now() >= deadline => return Err(elapsed),
+                Err(_) => {}
             }
         }
     }
+}
+
+/// Apply the post's reference, then publish it as a single post or a thread
+async fn publish(adapter: &dyn Platform, post: &Post) -> Result<PostOutcome, PostError> {
+    let post = with_reference(adapter, post).await?;
+    if post.thread.is_some() {
+        post_thread(adapter, &post).await
+    } else {
+        adapter.post(&post).await
+    }
+}
+
+/// Turn a reply/quote URL into a native reply or quote on the platform it
+/// belongs to, or a plain link everywhere else
+async fn with_reference(adapter: &dyn Platform, post: &Post) -> Result<Post, PostError> {
+    let mut post = post.clone();
+    let Some(reference) = post.reference.take() else {
+        return Ok(post);
+    };
     
-    async fn post_twitter(&self, post: &Post) -> (String, bool, String) {
-        if !self.credentials.has_twitter() {
-            return ("X".to_string(), false, "Not configured".to_string());
+    let native = reference.platform() == Some(adapter.id())
+        && (reference.kind == ReferenceKind::Reply || adapter.supports_quotes());
+    if native {
+        let target = adapter.resolve(&reference.url).await?;
+        match reference.kind {
+            ReferenceKind::Reply => post.reply = Some(target),
+            ReferenceKind::Quote => post.quote = Some(target.parent),
         }
-        
-        let platform = TwitterPlatform::new(
-            self.credentials.twitter_consumer_key.clone().unwrap(),
-            self.credentials.twitter_consumer_secret.clone().unwrap(),
-            self.credentials.twitter_access_token.clone().unwrap(),
-            self.credentials.twitter_access_secret.clone().unwrap(),
-        );
-        
-        match platform.post(post).await {
-            Ok(PostResult::Success { url }) => {
-                ("X".to_string(), true, url.unwrap_or_else(|| "Posted successfully".to_string()))
+    } else {
+        post.text = format!("{}\n\n{}", post.text.trim_end(), reference.url);
+    }
+    Ok(post)
+}
+
+/// Post text as a reply chain, split to fit the platform.
+///
+/// Returns the outcome of the first part, with the later parts in
+/// `thread_ids`. If the chain breaks after the first part went out, the
+/// outcome carries the error instead of failing, since what was published
+/// can't be retried without duplicating it.
+async fn post_thread(adapter: &dyn Platform, post: &Post) -> Result<PostOutcome, PostError> {
+    let options = post.thread.unwrap_or_default();
+    let parts = thread::split(adapter.id(), &post.text, options);
+    let total = parts.len();
+    
+    let mut first: Option<PostOutcome> = None;
+    let mut reply = post.reply.clone();
+    for (i, text) in parts.into_iter().enumerate() {
+        eprintln!("[{}] Posting thread part {}/{}", adapter.id(), i + 1, total);
+        // Images and quotes go with the first part only
+        let part = if i == 0 {
+            Post {
+                reply: reply.clone(),
+                quote: post.quote.clone(),
+                delivery_id: post.delivery_id.clone(),
+                ..Post::new(text, post.images.clone())
             }
-            Ok(PostResult::Error { message }) => {
-                ("X".to_string(), false, message)
+        } else {
+            Post {
+                reply: reply.clone(),
+                delivery_id: post.delivery_id.clone(),
+                ..Post::new(text, Vec::new())
             }
-            Err(e) => {
-                ("X".to_string(), false, format!("Error: {}", e))
+        };
+        
+        let outcome = match (adapter.post(&part).await, first.as_mut()) {
+            (Ok(outcome), _) => outcome,
+            (Err(e), None) => return Err(e),
+            (Err(e), Some(first_outcome)) => {
+                break_thread(first_outcome, i, total, e);
+                break;
             }
+        };
+        let id = outcome.remote_id.clone();
+        let first_outcome = first.get_or_insert(outcome);
+        let Some(id) = id else {
+            // The part is out, but the rest have nothing to reply to
+            if i + 1 < total {
+                let e = PostError::new(ErrorKind::Other, "Posted, but got no id to reply to");
+                break_thread(first_outcome, i + 1, total, e);
+            }
+            break;
+        };
+        if i > 0 {
+            first_outcome.thread_ids.push(id.clone());
         }
+        reply = Some(ReplyRef {
+            root: reply.map(|reply| reply.root).unwrap_or_else(|| id.clone()),
+            parent: id,
+        });
     }
     
-    async fn post_mastodon(&self, post: &Post) -> (String, bool, String) {
-        if !self.credentials.has_mastodon() {
-            return ("Mastodon".to_string(), false, "Not configured".to_string());
-        }
-        
-        let platform = MastodonPlatform::new(
-            self.credentials.mastodon_instance_url.clone().unwrap(),
-            self.credentials.mastodon_access_token.clone().unwrap(),
-        );
-        
-        match platform.post(post.text.clone(), &post.images).await {
-            Ok(url) => {
-                eprintln!("[Mastodon] Success: {}", url);
-                ("Mastodon".to_string(), true, url)
+    first.ok_or_else(|| PostError::new(ErrorKind::Validation, "Nothing to post"))
+}
+
+/// Record on the first part's outcome that the thread broke off after
+/// `posted` of `total` parts
+fn break_thread(outcome: &mut PostOutcome, posted: usize, total: usize, e: PostError) {
+    outcome.partial = true;
+    outcome.error = Some(PostError::new(
+        e.kind,
+        format!("Thread stopped after {} of {} parts: {}", posted, total, e.message),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platforms::ThreadOptions;
+    use crate::platforms::mastodon::MastodonPlatform;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Adapter that answers after `delay`, failing every post from the
+    /// `fail_from`th on
+    struct MockPlatform {
+        id: PlatformId,
+        delay: Duration,
+        fail_from: usize,
+        posts: AtomicUsize,
+        fail_deletes: bool,
+        deleted: Mutex<Vec<RemoteId>>,
+    }
+
+    impl MockPlatform {
+        fn new(id: PlatformId, delay: Duration) -> Self {
+            Self {
+                id,
+                delay,
+                fail_from: usize::MAX,
+                posts: AtomicUsize::new(0),
+                fail_deletes: false,
+                deleted: Mutex::new(Vec::new()),
             }
-            Err(e) => {
-                eprintln!("[Mastodon] Error: {}", e);
-                ("Mastodon".to_string(), false, format!("Error: {}", e))
+        }
+    }
+
+    impl Platform for MockPlatform {
+        fn id(&self) -> PlatformId {
+            self.id
+        }
+
+        fn is_authenticated(&self) -> BoxFuture<'_, bool> {
+            Box::pin(async { true })
+        }
+
+        fn post<'a>(&'a self, _post: &'a Post) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+            Box::pin(async move {
+                tokio::time::sleep(self.delay).await;
+                let n = self.posts.fetch_add(1, Ordering::SeqCst);
+                if n >= self.fail_from {
+                    return Err(PostError::new(ErrorKind::Network, "connection reset"));
+                }
+                Ok(PostOutcome {
+                    remote_id: Some(RemoteId::Status(n.to_string())),
+                    ..PostOutcome::success(self.id)
+                })
+            })
+        }
+
+        fn delete<'a>(&'a self, remote_id: &'a RemoteId) -> BoxFuture<'a, Result<PostOutcome, PostError>> {
+            Box::pin(async move {
+                if self.fail_deletes {
+                    return Err(PostError::new(ErrorKind::Network, "connection reset"));
+                }
+                self.deleted.lock().unwrap().push(remote_id.clone());
+                Ok(PostOutcome::success(self.id))
+            })
+        }
+
+        fn resolve<'a>(&'a self, _url: &'a str) -> BoxFuture<'a, Result<ReplyRef, PostError>> {
+            Box::pin(async { Err(PostError::new(ErrorKind::Validation, "not supported")) })
+        }
+    }
+
+    fn manager(adapters: Vec<MockPlatform>) -> PostManager {
+        let mut registry = PlatformRegistry::new();
+        for adapter in adapters {
+            registry.register(Box::new(adapter));
+        }
+        with_short_timeouts(registry)
+    }
+
+    fn with_short_timeouts(registry: PlatformRegistry) -> PostManager {
+        let timeout = Duration::from_millis(200);
+        PostManager::with_registry(registry).with_timeouts(PlatformTimeouts {
+            x: timeout,
+            bluesky: timeout,
+            nostr: timeout,
+            mastodon: timeout,
+        })
+    }
+
+    #[tokio::test]
+    async fn results_follow_requested_order() {
+        let manager = manager(vec![
+            MockPlatform::new(PlatformId::X, Duration::from_millis(50)),
+            MockPlatform::new(PlatformId::Mastodon, Duration::ZERO),
+        ]);
+        let platforms = vec![PlatformId::X, PlatformId::BlueSky, PlatformId::Mastodon];
+        let results = manager.post(Post::new("Hello".to_string(), Vec::new()), platforms.clone()).await;
+
+        let order: Vec<PlatformId> = results.iter().map(|outcome| outcome.platform).collect();
+        assert_eq!(order, platforms);
+        assert!(results[0].is_success());
+        assert_eq!(results[1].error.as_ref().unwrap().kind, ErrorKind::NotConfigured);
+        assert!(results[2].is_success());
+    }
+
+    #[tokio::test]
+    async fn slow_platform_times_out_alone() {
+        let manager = manager(vec![
+            MockPlatform::new(PlatformId::X, Duration::from_secs(5)),
+            MockPlatform::new(PlatformId::Mastodon, Duration::ZERO),
+        ]);
+        let results = manager
+            .post(Post::new("Hello".to_string(), Vec::new()), vec![PlatformId::X, PlatformId::Mastodon])
+            .await;
+
+        let error = results[0].error.as_ref().unwrap();
+        assert_eq!(error.kind, ErrorKind::Timeout);
+        // The request may have gone through, so it must not be resent blindly
+        assert!(!error.retry_safe);
+        assert!(results[1].is_success());
+    }
+
+    #[tokio::test]
+    async fn broken_thread_is_partial() {
+        let mut adapter = MockPlatform::new(PlatformId::Mastodon, Duration::ZERO);
+        adapter.fail_from = 1;
+        let manager = manager(vec![adapter]);
+        let post = Post {
+            thread: Some(ThreadOptions::default()),
+            ..Post::new("One\n---\nTwo\n---\nThree".to_string(), Vec::new())
+        };
+        let results = manager.post(post, vec![PlatformId::Mastodon]).await;
+
+        let outcome = &results[0];
+        assert!(outcome.partial);
+        assert_eq!(outcome.remote_id, Some(RemoteId::Status("0".to_string())));
+        assert!(outcome.error.as_ref().unwrap().message.starts_with("Thread stopped after 1 of 3 parts"));
+    }
+
+    /// Mastodon adapter pointed at a local server that accepts connections
+    /// but never answers. Accepted connections are handed to the test.
+    async fn silent_mastodon() -> (PostManager, tokio::sync::mpsc::UnboundedReceiver<tokio::net::TcpStream>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let _ = sender.send(stream);
             }
+        });
+
+        let mut registry = PlatformRegistry::new();
+        registry.register(Box::new(MastodonPlatform::new(url, "token".to_string())));
+        (with_short_timeouts(registry), receiver)
+    }
+
+    #[tokio::test]
+    async fn unresponsive_server_times_out() {
+        let (manager, _connections) = silent_mastodon().await;
+        let results = manager
+            .post(Post::new("Hello".to_string(), Vec::new()), vec![PlatformId::Mastodon])
+            .await;
+
+        let error = results[0].error.as_ref().unwrap();
+        assert_eq!(error.kind, ErrorKind::Timeout);
+        assert!(!error.retry_safe);
+    }
+
+    #[tokio::test]
+    async fn cancelled_post_closes_its_connection() {
+        use tokio::io::AsyncReadExt;
+
+        let (manager, mut connections) = silent_mastodon().await;
+        let posting = tokio::spawn(async move {
+            manager
+                .post(Post::new("Hello".to_string(), Vec::new()), vec![PlatformId::Mastodon])
+                .await
+        });
+        let mut stream = connections.recv().await.unwrap();
+        posting.abort();
+
+        // The request is dropped with the task, so the server sees it hang up
+        let mut request = Vec::new();
+        let read = tokio::time::timeout(Duration::from_secs(1), stream.read_to_end(&mut request)).await;
+        assert!(read.unwrap().is_ok());
+        assert!(String::from_utf8_lossy(&request).starts_with("POST /api/v1/statuses"));
+    }
+
+    fn target(thread_ids: &[&str]) -> HistoryTarget {
+        HistoryTarget {
+            platform: PlatformId::BlueSky,
+            url: None,
+            remote_id: Some(RemoteId::Status("root".to_string())),
+            thread_ids: thread_ids.iter().map(|id| RemoteId::Status(id.to_string())).collect(),
+            media_ids: Vec::new(),
+            deleted_at: None,
         }
     }
+
+    #[tokio::test]
+    async fn repost_deletes_the_whole_thread() {
+        let adapter = MockPlatform::new(PlatformId::BlueSky, Duration::ZERO);
+        let post = Post::new("Edited".to_string(), Vec::new());
+        let outcome = adapter.edit(&target(&["one", "two"]), &post).await.unwrap();
+
+        assert!(outcome.is_success());
+        let deleted: Vec<RemoteId> = ["two", "one", "root"]
+            .iter()
+            .map(|id| RemoteId::Status(id.to_string()))
+            .collect();
+        assert_eq!(*adapter.deleted.lock().unwrap(), deleted);
+    }
+
+    #[tokio::test]
+    async fn failed_delete_after_repost_is_partial() {
+        let mut adapter = MockPlatform::new(PlatformId::BlueSky, Duration::ZERO);
+        adapter.fail_deletes = true;
+        let post = Post::new("Edited".to_string(), Vec::new());
+        let outcome = adapter.edit(&target(&[]), &post).await.unwrap();
+
+        assert!(outcome.partial);
+        assert_eq!(outcome.remote_id, Some(RemoteId::Status("0".to_string())));
+        assert!(outcome.error.unwrap().message.starts_with("Reposted, but failed to delete"));
+    }
 }
@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: MIT
+
+//! Splitting long text into a thread that fits each platform's length limit
+
+use crate::platforms::{PlatformId, ThreadOptions};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A line containing only this forces a break between parts on every platform
+const MANUAL_BREAK: &str = "---";
+
+/// Length X and Mastodon count every link as, whatever its real length
+const LINK_LENGTH: usize = 23;
+
+/// Maximum post length, or `None` if the platform has no practical limit
+pub fn limit(platform: PlatformId) -> Option<usize> {
+    match platform {
+        PlatformId::X => Some(280),
+        PlatformId::BlueSky => Some(300),
+        PlatformId::Mastodon => Some(500),
+        PlatformId::Nostr => None,
+    }
+}
+
+/// Length of `text` as the platform counts it
+pub fn length(platform: PlatformId, text: &str) -> usize {
+    match platform {
+        // BlueSky limits graphemes
+        PlatformId::BlueSky => text.graphemes(true).count(),
+        PlatformId::Nostr => text.chars().count(),
+        // X weighs most non-Latin characters double
+        PlatformId::X => length_with_links(text, |c| match u32::from(c) {
+            0..=0x10FF | 0x2000..=0x200D | 0x2010..=0x201F | 0x2032..=0x2037 => 1,
+            _ => 2,
+        }),
+        PlatformId::Mastodon => length_with_links(text, |_| 1),
+    }
+}
+
+/// Count characters by weight, with each link counting as [`LINK_LENGTH`]
+fn length_with_links(text: &str, weight: impl Fn(char) -> usize) -> usize {
+    let mut total = 0;
+    let mut rest = text;
+    for word in text.split_whitespace() {
+        if !(word.starts_with("https://") || word.starts_with("http://")) {
+            continue;
+        }
+        let start = word.as_ptr() as usize - rest.as_ptr() as usize;
+        total += rest[..start].chars().map(&weight).sum::<usize>() + LINK_LENGTH;
+        rest = &rest[start + word.len()..];
+    }
+    total + rest.chars().map(&weight).sum::<usize>()
+}
+
+/// Split text into the parts of a thread for one platform.
+///
+/// Lines reading `---` always start a new part. Anything still too long is
+/// broken at paragraph, then sentence, then word boundaries.
+pub fn split(platform: PlatformId, text: &str, options: ThreadOptions) -> Vec<String> {
+    let sections: Vec<&str> = split_manual_breaks(text);
+
+    // Leave room for the "n/m" counter, growing it until the count fits
+    let mut reserve_for = 9;
+    loop {
+        let reserve = if options.numbered {
+            length(platform, &counter(reserve_for, reserve_for))
+        } else {
+            0
+        };
+        let fits = |part: &str| match limit(platform) {
+            Some(limit) => length(platform, part) + reserve <= limit,
+            None => true,
+        };
+
+        let parts: Vec<String> = sections
+            .iter()
+            .flat_map(|section| pack(section, &fits, Level::Paragraph))
+            .collect();
+
+        if !options.numbered || parts.len() <= 1 {
+            return parts;
+        }
+        if parts.len() <= reserve_for {
+            let total = parts.len();
+            return parts
+                .into_iter()
+                .enumerate()
+                .map(|(i, part)| format!("{}{}", part, counter(i + 1, total)))
+                .collect();
+        }
+        reserve_for = reserve_for * 10 + 9;
+    }
+}
+
+fn counter(index: usize, total: usize) -> String {
+    format!(" {}/{}", index, total)
+}
+
+fn split_manual_breaks(text: &str) -> Vec<&str> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim() == MANUAL_BREAK {
+            sections.push(&text[start..offset]);
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    sections.push(&text[start..]);
+
+    sections
+        .into_iter()
+        .map(str::trim)
+        .filter(|section| !section.is_empty())
+        .collect()
+}
+
+/// Boundaries tried in order when a piece is too long
+#[derive(Clone, Copy)]
+enum Level {
+    Paragraph,
+    Sentence,
+    Word,
+    Grapheme,
+}
+
+impl Level {
+    fn next(self) -> Self {
+        match self {
+            Level::Paragraph => Level::Sentence,
+            Level::Sentence => Level::Word,
+            Level::Word | Level::Grapheme => Level::Grapheme,
+        }
+    }
+
+    fn pieces(self, text: &str) -> (Vec<&str>, &'static str) {
+        match self {
+            Level::Paragraph => (
+                text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()).collect(),
+                "\n\n",
+            ),
+            Level::Sentence => (
+                text.split_sentence_bounds().map(str::trim).filter(|s| !s.is_empty()).collect(),
+                " ",
+            ),
+            Level::Word => (text.split_whitespace().collect(), " "),
+            Level::Grapheme => (text.graphemes(true).collect(), ""),
+        }
+    }
+}
+
+/// Greedily join pieces into parts that fit, breaking oversized pieces at
+/// the next finer boundary
+fn pack(text: &str, fits: &dyn Fn(&str) -> bool, level: Level) -> Vec<String> {
+    if fits(text) {
+        return vec![text.to_string()];
+    }
+
+    let (pieces, separator) = level.pieces(text);
+    let mut parts = Vec::new();
+    let mut current = String::new();
+
+    for piece in pieces {
+        let candidate = if current.is_empty() {
+            piece.to_string()
+        } else {
+            format!("{}{}{}", current, separator, piece)
+        };
+        if fits(&candidate) {
+            current = candidate;
+            continue;
+        }
+
+        if !current.is_empty() {
+            parts.push(std::mem::take(&mut current));
+        }
+        if fits(piece) {
+            current = piece.to_string();
+        } else if let Level::Grapheme = level {
+            // A single grapheme over the limit can't be split any further
+            parts.push(piece.to_string());
+        } else {
+            let mut sub = pack(piece, fits, level.next());
+            current = sub.pop().unwrap_or_default();
+            parts.extend(sub);
+        }
+    }
+
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NUMBERED: ThreadOptions = ThreadOptions { numbered: true };
+
+    #[test]
+    fn short_text_stays_whole() {
+        assert_eq!(split(PlatformId::X, "Hello", NUMBERED), vec!["Hello"]);
+    }
+
+    #[test]
+    fn manual_breaks_split_everywhere() {
+        let text = "First\n---\nSecond\n  ---  \nThird";
+        let parts = split(PlatformId::Nostr, text, ThreadOptions::default());
+        assert_eq!(parts, vec!["First", "Second", "Third"]);
+    }
+
+    #[test]
+    fn breaks_at_paragraphs_first() {
+        let first = "a ".repeat(100);
+        let second = "b ".repeat(100);
+        let text = format!("{}\n\n{}", first.trim(), second.trim());
+        let parts = split(PlatformId::X, &text, ThreadOptions::default());
+        assert_eq!(parts, vec![first.trim(), second.trim()]);
+    }
+
+    #[test]
+    fn parts_fit_with_counter() {
+        let text = "word ".repeat(200);
+        let parts = split(PlatformId::X, &text, NUMBERED);
+        assert!(parts.len() > 1);
+        for (i, part) in parts.iter().enumerate() {
+            assert!(length(PlatformId::X, part) <= 280, "{}", part);
+            assert!(part.ends_with(&format!(" {}/{}", i + 1, parts.len())));
+        }
+    }
+
+    #[test]
+    fn long_word_breaks_at_graphemes() {
+        let text = "é".repeat(301);
+        let parts = split(PlatformId::BlueSky, &text, ThreadOptions::default());
+        assert_eq!(parts, vec!["é".repeat(300), "é".to_string()]);
+    }
+
+    #[test]
+    fn links_count_as_fixed_length() {
+        let link = format!("https://example.com/{}", "a".repeat(100));
+        assert_eq!(length(PlatformId::X, &format!("see {}", link)), 4 + LINK_LENGTH);
+        assert_eq!(length(PlatformId::Mastodon, &link), LINK_LENGTH);
+        assert_eq!(length(PlatformId::BlueSky, &link), link.len());
+    }
+
+    #[test]
+    fn x_weighs_cjk_double() {
+        assert_eq!(length(PlatformId::X, "日本"), 4);
+        assert_eq!(length(PlatformId::Nostr, "日本"), 2);
+    }
+}